@@ -1,7 +1,12 @@
 use std::str::FromStr;
 
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use tripolys::{adjacency_list::AdjacencyList, consistency::sac_1, triad::Triad};
+use tripolys::{
+    adjacency_list::AdjacencyList,
+    consistency::{ac_2001, ac_3, sac_1},
+    dense_graph::DenseGraph,
+    triad::Triad,
+};
 
 fn sac1_4(c: &mut Criterion) {
     let triad = Triad::from_str("0,0,0").unwrap();
@@ -12,5 +17,34 @@ fn sac1_4(c: &mut Criterion) {
     });
 }
 
-criterion_group!(sac_1, sac1_4);
-criterion_main!(sac_1);
+fn sac1_4_dense(c: &mut Criterion) {
+    let triad = Triad::from_str("0,0,0").unwrap();
+    let graph = DenseGraph::<u32>::from(&triad);
+
+    c.bench_function("sac1_4_dense", |b| {
+        b.iter(|| sac_1(black_box(&graph), black_box(&graph)))
+    });
+}
+
+fn ac3_power(c: &mut Criterion) {
+    let triad = Triad::from_str("0111,00,1").unwrap();
+    let list = AdjacencyList::<u32>::from(&triad);
+    let power = list.power(2);
+
+    c.bench_function("ac3_power", |b| {
+        b.iter(|| ac_3(black_box(&power), black_box(&list)))
+    });
+}
+
+fn ac2001_power(c: &mut Criterion) {
+    let triad = Triad::from_str("0111,00,1").unwrap();
+    let list = AdjacencyList::<u32>::from(&triad);
+    let power = list.power(2);
+
+    c.bench_function("ac2001_power", |b| {
+        b.iter(|| ac_2001(black_box(&power), black_box(&list)))
+    });
+}
+
+criterion_group!(benches, sac1_4, sac1_4_dense, ac3_power, ac2001_power);
+criterion_main!(benches);
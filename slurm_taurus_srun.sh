@@ -13,6 +13,7 @@
 srun ./target/release/tripolys \
 	--data /scratch/ws/0/s8179597-triads/data \
 	--triad 10110000,0101111,10011 \
-	--polymorphism 3/4wnu
+	--polymorphism 3/4wnu \
+	--parallel split
 
 scontrol show job "$SLURM_JOB_ID"
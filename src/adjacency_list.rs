@@ -10,10 +10,48 @@ use std::{
 
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 
-pub trait VertexID: Eq + Clone + Hash {}
+use crate::{
+    consistency::{ac_3_lists, backtrack_search_lists, Lists},
+    graph::{Graph, MutableGraph},
+    metrics::Metrics,
+};
+
+pub trait VertexID: Ord + Clone + Hash {}
 impl VertexID for u32 {}
+impl VertexID for u64 {}
 impl<T: VertexID> VertexID for Vec<T> {}
 
+/// Vertices that are identified by a dense index, i.e. the vertices of a graph
+/// with n vertices have the indices 0..n. This allows `consistency::List` to
+/// store them as bits.
+pub trait DenseID: VertexID {
+    /// Returns the index of the vertex.
+    fn index(&self) -> usize;
+
+    /// Returns the vertex with the given index.
+    fn from_index(index: usize) -> Self;
+}
+
+impl DenseID for u32 {
+    fn index(&self) -> usize {
+        *self as usize
+    }
+
+    fn from_index(index: usize) -> Self {
+        index as u32
+    }
+}
+
+impl DenseID for u64 {
+    fn index(&self) -> usize {
+        *self as usize
+    }
+
+    fn from_index(index: usize) -> Self {
+        index as u64
+    }
+}
+
 /// A simple set implemented as a wrapper around Vec.
 #[derive(Clone, Debug, Default)]
 pub struct Set<T: Eq> {
@@ -55,7 +93,7 @@ impl<T: Eq> Set<T> {
 
     /// An iterator visiting all elements in arbitrary order.
     /// The iterator element type is `(&'a T)`.
-    pub fn iter<'a>(&'a self) -> impl Iterator<Item = &T> + 'a {
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
         self.items.iter()
     }
 
@@ -82,12 +120,18 @@ impl<T: Eq> FromIterator<T> for Set<T> {
 /// around a `HashMap`. For each vertex the `HashMap` contains an ordered pair, the
 /// adjacency lists, where the first entry and second entry contain all
 /// successors and predecessors, respectively.
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone)]
 pub struct AdjacencyList<V: VertexID> {
     // Vertex -> (Out-Edges, In-Edges)
     adjacency_list: HashMap<V, (Set<V>, Set<V>)>,
 }
 
+impl<V: VertexID> Default for AdjacencyList<V> {
+    fn default() -> Self {
+        AdjacencyList::new()
+    }
+}
+
 impl<V: VertexID> AdjacencyList<V> {
     /// Creates an empty `AdjacencyList`.
     pub fn new() -> AdjacencyList<V> {
@@ -115,13 +159,14 @@ impl<V: VertexID> AdjacencyList<V> {
     pub fn remove_vertex(&mut self, v: &V) -> Option<(Set<V>, Set<V>)> {
         // remove vertex
         if let Some((out_edges, in_edges)) = self.adjacency_list.remove(v) {
-            // remove vertex from out-edge list of other vertices
-            for u in in_edges.iter() {
+            // remove vertex from out-edge list of other vertices, a loop has
+            // already been removed with the vertex
+            for u in in_edges.iter().filter(|u| *u != v) {
                 self.adjacency_list.get_mut(u).unwrap().0.remove(v);
             }
 
             // remove vertex from in-edge list of other vertices
-            for u in out_edges.iter() {
+            for u in out_edges.iter().filter(|u| *u != v) {
                 self.adjacency_list.get_mut(u).unwrap().1.remove(v);
             }
 
@@ -141,12 +186,13 @@ impl<V: VertexID> AdjacencyList<V> {
         assert!(u != v, "vertex can not be contracted with itself!");
         let (out_edges, in_edges) = self.remove_vertex(v).unwrap();
 
+        // a loop at `v` becomes a loop at `u`
         for w in in_edges.iter() {
-            self.add_edge(w, u);
+            self.add_edge(if w == v { u } else { w }, u);
         }
 
         for w in out_edges.iter() {
-            self.add_edge(u, w);
+            self.add_edge(u, if w == v { u } else { w });
         }
     }
 
@@ -201,7 +247,7 @@ impl<V: VertexID> AdjacencyList<V> {
     }
 
     /// Returns an iterator over references to all of the vertices in the graph.
-    pub fn vertices<'a>(&'a self) -> impl Iterator<Item = &V> + 'a {
+    pub fn vertices(&self) -> impl Iterator<Item = &V> {
         self.adjacency_list.keys()
     }
 
@@ -261,7 +307,7 @@ impl<V: VertexID> AdjacencyList<V> {
         let mut map1 = self.adjacency_list.clone();
         let map2 = l.adjacency_list.clone();
 
-        map1.extend(map2.into_iter());
+        map1.extend(map2);
         AdjacencyList {
             adjacency_list: map1,
         }
@@ -359,18 +405,116 @@ impl<T: VertexID + Debug> AdjacencyList<T> {
     }
 }
 
+impl<T: DenseID + Debug> AdjacencyList<T> {
+    /// Computes the core of the graph, i.e. a subgraph C such that the graph
+    /// retracts to C and every endomorphism of C is surjective. Returns the
+    /// core and the retraction, which maps every vertex of the graph to a
+    /// vertex of the core and is the identity on the core.
+    ///
+    /// The graph is repeatedly replaced by the image of a non-surjective
+    /// endomorphism. Such an endomorphism is searched for each vertex w by a
+    /// backtracking search on arc-consistent lists that don't contain w.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tripolys::adjacency_list::AdjacencyList;
+    ///
+    /// // The path 0 -> 1 <- 2 retracts to the edge 0 -> 1
+    /// let mut graph = AdjacencyList::<u32>::new();
+    /// for v in 0..3 {
+    ///     graph.add_vertex(v);
+    /// }
+    /// graph.add_edge(&0, &1);
+    /// graph.add_edge(&2, &1);
+    ///
+    /// let (core, retraction) = graph.core();
+    /// assert_eq!(core.vertices().count(), 2);
+    /// assert_eq!(retraction[&1], 1);
+    /// assert_eq!(retraction[&0], retraction[&2]);
+    ///
+    /// // A graph with a loop retracts to the loop
+    /// let mut graph = AdjacencyList::<u32>::new();
+    /// for v in 0..2 {
+    ///     graph.add_vertex(v);
+    /// }
+    /// graph.add_edge(&0, &0);
+    /// graph.add_edge(&1, &1);
+    /// graph.add_edge(&0, &1);
+    ///
+    /// let (core, retraction) = graph.core();
+    /// assert_eq!(core.vertices().count(), 1);
+    /// assert!(core.has_edge(&retraction[&0], &retraction[&1]));
+    /// ```
+    pub fn core(&self) -> (AdjacencyList<T>, HashMap<T, T>) {
+        let mut core = self.clone();
+        let mut map = self
+            .vertices()
+            .map(|v| (v.clone(), v.clone()))
+            .collect::<HashMap<_, _>>();
+
+        while let Some(f) = core.non_surjective_endomorphism() {
+            for w in map.values_mut() {
+                *w = f[w].clone();
+            }
+            let image = f.values().cloned().collect::<HashSet<_>>();
+            let removed = core
+                .vertices()
+                .filter(|v| !image.contains(v))
+                .cloned()
+                .collect::<Vec<_>>();
+            for v in &removed {
+                core.remove_vertex(v);
+            }
+        }
+
+        // The map restricted to the core is an automorphism of the core, undo it
+        // to obtain a retraction.
+        let inverse = core
+            .vertices()
+            .map(|v| (map[v].clone(), v.clone()))
+            .collect::<HashMap<_, _>>();
+        for w in map.values_mut() {
+            *w = inverse[w].clone();
+        }
+        (core, map)
+    }
+
+    // Searches for an endomorphism of the graph that is not surjective.
+    fn non_surjective_endomorphism(&self) -> Option<HashMap<T, T>> {
+        let lists = ac_3_lists(self, self, Lists::new())?;
+        let variables = lists.variables().cloned().collect::<Vec<_>>();
+
+        self.vertices().find_map(|w| {
+            let mut lists = lists.clone();
+            for v in &variables {
+                lists.remove(v, w);
+            }
+            let res = backtrack_search_lists(self, self, lists, &mut Metrics::default())?;
+            Some(
+                res.iter()
+                    .map(|(v, l)| (v.clone(), l.iter().next().unwrap().clone()))
+                    .collect(),
+            )
+        })
+    }
+}
+
 impl<T: VertexID + Sync + Send> AdjacencyList<T> {
     /// Returns the k-ary product graph. The resulting graph uses `Vec` to represent
     /// the resulting tuples. The method uses parallelism.
     /// # Examples
     ///
     /// ```rust
-    /// let mut graph = AdjacencyList::new();
+    /// use tripolys::adjacency_list::AdjacencyList;
+    ///
+    /// let mut graph = AdjacencyList::<u32>::new();
     /// graph.add_vertex(0);
     /// graph.add_vertex(1);
     /// graph.add_edge(&0, &1);
     ///
     /// let graph2 = graph.power(2);
+    /// let mut vertices = Vec::new();
     ///
     /// // Iterate over edges
     /// for (u, v) in graph2.edges() {
@@ -378,7 +522,8 @@ impl<T: VertexID + Sync + Send> AdjacencyList<T> {
     ///     vertices.push(v);
     /// }
     ///
-    /// assert_eq!(vertices.len(), 4);
+    /// assert_eq!(graph2.vertices().count(), 4);
+    /// assert_eq!(vertices, vec![vec![0, 0], vec![1, 1]]);
     /// ```
     pub fn power(&self, k: u32) -> AdjacencyList<Vec<T>> {
         let mut graph = AdjacencyList::new();
@@ -420,3 +565,67 @@ impl<T: VertexID + Sync + Send> AdjacencyList<T> {
         graph
     }
 }
+
+impl<V: VertexID> Graph for AdjacencyList<V> {
+    type Vertex = V;
+
+    fn vertices(&self) -> impl Iterator<Item = &V> {
+        AdjacencyList::vertices(self)
+    }
+
+    fn vertex_count(&self) -> usize {
+        self.adjacency_list.len()
+    }
+
+    fn has_vertex(&self, v: &V) -> bool {
+        AdjacencyList::has_vertex(self, v)
+    }
+
+    fn edges(&self) -> impl Iterator<Item = (V, V)> {
+        AdjacencyList::edges(self)
+    }
+
+    fn has_edge(&self, u: &V, v: &V) -> bool {
+        AdjacencyList::has_edge(self, u, v)
+    }
+
+    fn out_neighbors(&self, v: &V) -> impl Iterator<Item = V> {
+        self.adjacency_list.get(v).unwrap().0.iter().cloned()
+    }
+
+    fn in_neighbors(&self, v: &V) -> impl Iterator<Item = V> {
+        self.adjacency_list.get(v).unwrap().1.iter().cloned()
+    }
+
+    fn degree(&self, v: &V) -> usize {
+        AdjacencyList::degree(self, v)
+    }
+}
+
+impl<V: VertexID + Sync + Send> MutableGraph for AdjacencyList<V> {
+    type Power = AdjacencyList<Vec<V>>;
+
+    fn add_vertex(&mut self, v: V) -> bool {
+        AdjacencyList::add_vertex(self, v)
+    }
+
+    fn add_edge(&mut self, u: &V, v: &V) -> bool {
+        AdjacencyList::add_edge(self, u, v)
+    }
+
+    fn contract_vertices(&mut self, u: &V, v: &V) {
+        AdjacencyList::contract_vertices(self, u, v);
+    }
+
+    fn union(&self, other: &Self) -> Self {
+        AdjacencyList::union(self, other)
+    }
+
+    fn components(&self) -> Vec<Self> {
+        AdjacencyList::components(self)
+    }
+
+    fn power(&self, k: u32) -> Self::Power {
+        AdjacencyList::power(self, k)
+    }
+}
@@ -0,0 +1,576 @@
+//! Crash-safe files for caching generated data such as arms, pairs and cores.
+//!
+//! The first line of a cache file is a header that records the format version,
+//! the parameters the data was generated with, the number of entries and a
+//! checksum of the body. A file is only reused if its header validates, so a
+//! file that was truncated by a crash is detected and regenerated.
+//!
+//! The body is either text, one entry per line, or a compact binary encoding
+//! with bit-packed arms and varint indices. Which of the two is written is
+//! selected per data directory by the file `format` in it, see
+//! [`Format::of`](enum.Format.html#method.of). Files of both formats are read
+//! regardless of that selection.
+use std::{
+    error::Error,
+    fmt,
+    fs::{self, File},
+    io::{self, Write},
+    ops::Deref,
+    path::Path,
+    str::FromStr,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// The version of the cache file format.
+pub const VERSION: u32 = 1;
+
+const MAGIC_TEXT: &str = "#tripolys-cache";
+const MAGIC_BINARY: &str = "#tripolys-cache-bin";
+
+/// The encoding of the body of a cache file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    /// One entry per line, fields separated by commas
+    #[default]
+    Text,
+    /// Bit-packed arms and varint indices
+    Binary,
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Format::Text => write!(f, "text"),
+            Format::Binary => write!(f, "binary"),
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "binary" => Ok(Format::Binary),
+            _ => Err("Unknown cache format"),
+        }
+    }
+}
+
+impl Format {
+    /// Returns the format that is selected for the data directory `dir`. It is
+    /// read from the file `format` in `dir` and defaults to text.
+    pub fn of(dir: &str) -> Format {
+        fs::read_to_string(format!("{}/format", dir))
+            .ok()
+            .and_then(|s| s.trim().parse().ok())
+            .unwrap_or_default()
+    }
+
+    /// Selects the format for the data directory `dir`.
+    pub fn select(self, dir: &str) -> io::Result<()> {
+        fs::create_dir_all(dir)?;
+        fs::write(format!("{}/format", dir), format!("{}\n", self))
+    }
+
+    fn magic(self) -> &'static str {
+        match self {
+            Format::Text => MAGIC_TEXT,
+            Format::Binary => MAGIC_BINARY,
+        }
+    }
+}
+
+/// A type that can be stored in a cache file.
+pub trait Entry: Sized {
+    /// The kind of cache file that holds entries of this type.
+    const KIND: &'static str;
+
+    /// Returns the line that represents the entry in a text file.
+    fn to_text(&self) -> String;
+
+    /// Parses an entry from a line of a text file.
+    fn from_text(line: &str) -> Option<Self>;
+
+    /// Appends the binary encoding of the entry to `buf`.
+    fn encode(&self, buf: &mut Vec<u8>);
+
+    /// Decodes an entry from `bytes`, starting at `pos`, and advances `pos`
+    /// past it.
+    fn decode(bytes: &[u8], pos: &mut usize) -> Option<Self>;
+}
+
+/// A cache file at `path` holding entries that were generated with the
+/// parameters `params`.
+#[derive(Debug, Clone)]
+pub struct CacheFile {
+    path: String,
+    params: Vec<(String, String)>,
+    format: Format,
+}
+
+/// The reasons why a cache file can not be reused.
+#[derive(Debug)]
+pub enum CacheError {
+    /// The file does not exist
+    Missing,
+    /// The file could not be read
+    Io(io::Error),
+    /// The header is missing or malformed
+    MalformedHeader,
+    /// The file was written with another format version
+    Version(String),
+    /// The file was generated with other parameters
+    Parameters(String),
+    /// The file holds a different number of entries than recorded
+    Count { expected: usize, found: usize },
+    /// The body doesn't match the recorded checksum
+    Checksum,
+    /// An entry could not be decoded
+    MalformedEntry(usize),
+}
+
+impl fmt::Display for CacheError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CacheError::Missing => write!(f, "File does not exist"),
+            CacheError::Io(e) => write!(f, "{}", e),
+            CacheError::MalformedHeader => write!(f, "Header is missing or malformed"),
+            CacheError::Version(v) => write!(f, "Unsupported format version {}", v),
+            CacheError::Parameters(p) => write!(f, "Generated with other parameters: {}", p),
+            CacheError::Count { expected, found } => {
+                write!(f, "Expected {} entries, found {}", expected, found)
+            }
+            CacheError::Checksum => write!(f, "Checksum does not match"),
+            CacheError::MalformedEntry(i) => write!(f, "Entry {} is malformed", i),
+        }
+    }
+}
+
+impl Error for CacheError {}
+
+impl From<CacheError> for io::Error {
+    fn from(e: CacheError) -> Self {
+        match e {
+            CacheError::Io(e) => e,
+            CacheError::Missing => io::Error::new(io::ErrorKind::NotFound, e),
+            e => io::Error::new(io::ErrorKind::InvalidData, e),
+        }
+    }
+}
+
+// The fields of a validated header line.
+struct Header {
+    format: Format,
+    kind: String,
+    params: Vec<(String, String)>,
+    entries: usize,
+    checksum: String,
+}
+
+impl CacheFile {
+    /// Creates a handle for the cache file at `path`. Entries are written in
+    /// the format `format`.
+    pub fn new(path: String, params: &[(&str, String)], format: Format) -> CacheFile {
+        CacheFile {
+            path,
+            params: params
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.clone()))
+                .collect(),
+            format,
+        }
+    }
+
+    /// Returns the path of the cache file.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Reads the entries of the cache file, if its header validates.
+    pub fn read<T: Entry>(&self) -> Result<Vec<T>, CacheError> {
+        let content = contents(&self.path)?;
+        let (header, body) = split_header(&content)?;
+
+        if header.kind != T::KIND {
+            return Err(CacheError::Parameters(format!("kind={}", header.kind)));
+        }
+        for (key, value) in &self.params {
+            match header.params.iter().find(|(k, _)| k == key) {
+                Some((_, found)) if found == value => {}
+                Some((_, found)) => {
+                    return Err(CacheError::Parameters(format!("{}={}", key, found)))
+                }
+                None => return Err(CacheError::MalformedHeader),
+            }
+        }
+        decode_body(&header, body)
+    }
+
+    /// Reads the entries of the cache file. Returns None, if the file is
+    /// missing or doesn't validate, the latter is reported on stderr.
+    pub fn load<T: Entry>(&self) -> Option<Vec<T>> {
+        match self.read() {
+            Ok(entries) => Some(entries),
+            Err(CacheError::Missing) => None,
+            Err(e) => {
+                eprintln!("Regenerating invalid cache file {}: {}", self.path, e);
+                None
+            }
+        }
+    }
+
+    /// Writes `entries` to the cache file. The data is written to a temporary
+    /// file first, which is then atomically renamed, so that the cache file is
+    /// either complete or not there at all. The temporary file is unique to the
+    /// process, so that jobs sharing a data directory don't interfere.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use tripolys::cache_file::{CacheError, CacheFile, Format};
+    ///
+    /// let path = std::env::temp_dir().join("tripolys-doctest-arms");
+    /// let cache = CacheFile::new(path.to_string_lossy().into(), &[], Format::Text);
+    /// let arms = vec!["01".to_string(), "0011".to_string(), "1".to_string()];
+    /// cache.write(&arms).unwrap();
+    /// assert_eq!(cache.read::<String>().unwrap(), arms);
+    ///
+    /// // a truncated file is detected
+    /// let content = std::fs::read(&path).unwrap();
+    /// std::fs::write(&path, &content[..content.len() - 3]).unwrap();
+    /// assert!(matches!(
+    ///     cache.read::<String>(),
+    ///     Err(CacheError::Count { .. }) | Err(CacheError::Checksum)
+    /// ));
+    /// std::fs::remove_file(&path).unwrap();
+    /// ```
+    pub fn write<T: Entry>(&self, entries: &[T]) -> io::Result<()> {
+        let mut body = Vec::new();
+        match self.format {
+            Format::Text => {
+                for entry in entries {
+                    body.extend_from_slice(entry.to_text().as_bytes());
+                    body.push(b'\n');
+                }
+            }
+            Format::Binary => {
+                for entry in entries {
+                    entry.encode(&mut body);
+                }
+            }
+        }
+
+        let parent = match Path::new(&self.path).parent() {
+            Some(parent) if parent != Path::new("") => parent,
+            _ => Path::new("."),
+        };
+        fs::create_dir_all(parent)?;
+        // Threads of the same process may write the same file as well
+        static WRITES: AtomicUsize = AtomicUsize::new(0);
+        let tmp = format!(
+            "{}.{}.{}.tmp",
+            self.path,
+            std::process::id(),
+            WRITES.fetch_add(1, Ordering::Relaxed)
+        );
+        let mut file = io::BufWriter::new(File::create(&tmp)?);
+
+        write!(
+            file,
+            "{} version={} kind={}",
+            self.format.magic(),
+            VERSION,
+            T::KIND
+        )?;
+        for (key, value) in &self.params {
+            write!(file, " {}={}", key, value)?;
+        }
+        writeln!(
+            file,
+            " entries={} checksum={:016x}",
+            entries.len(),
+            checksum_of(&body)
+        )?;
+        file.write_all(&body)?;
+        file.into_inner()?.sync_all()?;
+        fs::rename(&tmp, &self.path)?;
+        // The rename is only durable once the directory is synced
+        #[cfg(unix)]
+        File::open(parent)?.sync_all()?;
+        Ok(())
+    }
+}
+
+/// Reads the entries of the cache file at `path` without checking the
+/// parameters it was generated with. Returns None, if the file has no header.
+pub fn read_any<T: Entry>(path: &str) -> Result<Option<Vec<T>>, CacheError> {
+    let content = contents(path)?;
+    let (header, body) = match split_header(&content) {
+        Ok(split) => split,
+        Err(CacheError::MalformedHeader) if !content.starts_with(MAGIC_TEXT.as_bytes()) => {
+            return Ok(None)
+        }
+        Err(e) => return Err(e),
+    };
+    if header.kind != T::KIND {
+        return Err(CacheError::Parameters(format!("kind={}", header.kind)));
+    }
+    decode_body(&header, body).map(Some)
+}
+
+/// Returns the kind of the cache file at `path`, or None if it has no header.
+pub fn kind_of(path: &str) -> Option<String> {
+    let content = contents(path).ok()?;
+    split_header(&content).ok().map(|(header, _)| header.kind)
+}
+
+/// Rewrites the cache file at `path`, which holds entries of type `T`, in the
+/// format `format`. Returns `false`, if it already was in that format.
+pub fn convert<T: Entry>(path: &str, format: Format) -> Result<bool, CacheError> {
+    let (params, entries) = {
+        let content = contents(path)?;
+        let (header, body) = split_header(&content)?;
+        if header.format == format {
+            return Ok(false);
+        }
+        if header.kind != T::KIND {
+            return Err(CacheError::Parameters(format!("kind={}", header.kind)));
+        }
+        (header.params.clone(), decode_body::<T>(&header, body)?)
+    };
+    let params = params
+        .iter()
+        .map(|(k, v)| (k.as_str(), v.clone()))
+        .collect::<Vec<_>>();
+    CacheFile::new(path.to_string(), &params, format)
+        .write(&entries)
+        .map_err(CacheError::Io)?;
+    Ok(true)
+}
+
+/// Adds a header to the cache file at `path`, which was written without one by
+/// an earlier version, holds one entry of type `T` per line and was generated
+/// with the parameters `params`. The file is rewritten in the format `format`.
+/// Returns `false`, if it already has a header.
+pub fn import_legacy<T: Entry>(
+    path: &str,
+    params: &[(&str, String)],
+    format: Format,
+) -> Result<bool, CacheError> {
+    let entries = {
+        let content = contents(path)?;
+        if content.starts_with(MAGIC_TEXT.as_bytes()) {
+            return Ok(false);
+        }
+        let body = std::str::from_utf8(&content).map_err(|_| CacheError::MalformedEntry(0))?;
+        body.split_terminator('\n')
+            .enumerate()
+            .map(|(i, line)| T::from_text(line).ok_or(CacheError::MalformedEntry(i)))
+            .collect::<Result<Vec<_>, _>>()?
+    };
+    CacheFile::new(path.to_string(), params, format)
+        .write(&entries)
+        .map_err(CacheError::Io)?;
+    Ok(true)
+}
+
+// The contents of a file, memory-mapped if the feature `mmap` is enabled.
+enum Contents {
+    #[cfg(not(feature = "mmap"))]
+    Buffer(Vec<u8>),
+    #[cfg(feature = "mmap")]
+    Mapped(memmap2::Mmap),
+}
+
+impl Deref for Contents {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            #[cfg(not(feature = "mmap"))]
+            Contents::Buffer(buf) => buf,
+            #[cfg(feature = "mmap")]
+            Contents::Mapped(map) => map,
+        }
+    }
+}
+
+fn contents(path: &str) -> Result<Contents, CacheError> {
+    let not_found = |e: io::Error| {
+        if e.kind() == io::ErrorKind::NotFound {
+            CacheError::Missing
+        } else {
+            CacheError::Io(e)
+        }
+    };
+    #[cfg(feature = "mmap")]
+    {
+        let file = File::open(path).map_err(not_found)?;
+        // Safety: cache files are only ever replaced by renaming, never
+        // modified in place, so the mapping can't change underneath us.
+        let map = unsafe { memmap2::Mmap::map(&file) }.map_err(CacheError::Io)?;
+        Ok(Contents::Mapped(map))
+    }
+    #[cfg(not(feature = "mmap"))]
+    {
+        fs::read(path).map(Contents::Buffer).map_err(not_found)
+    }
+}
+
+// Splits `content` into the parsed header line and the body.
+fn split_header(content: &[u8]) -> Result<(Header, &[u8]), CacheError> {
+    let end = content
+        .iter()
+        .position(|&b| b == b'\n')
+        .ok_or(CacheError::MalformedHeader)?;
+    let line = std::str::from_utf8(&content[..end]).map_err(|_| CacheError::MalformedHeader)?;
+
+    let mut fields = line.split(' ');
+    let format = match fields.next() {
+        Some(MAGIC_TEXT) => Format::Text,
+        Some(MAGIC_BINARY) => Format::Binary,
+        _ => return Err(CacheError::MalformedHeader),
+    };
+    let mut params = fields
+        .map(|field| {
+            let mut kv = field.splitn(2, '=');
+            match (kv.next(), kv.next()) {
+                (Some(k), Some(v)) => Ok((k.to_string(), v.to_string())),
+                _ => Err(CacheError::MalformedHeader),
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut field = |key: &str| {
+        let i = params
+            .iter()
+            .position(|(k, _)| k == key)
+            .ok_or(CacheError::MalformedHeader)?;
+        Ok(params.remove(i).1)
+    };
+
+    let version = field("version")?;
+    if version != VERSION.to_string() {
+        return Err(CacheError::Version(version));
+    }
+    let kind = field("kind")?;
+    let entries = field("entries")?
+        .parse::<usize>()
+        .map_err(|_| CacheError::MalformedHeader)?;
+    let checksum = field("checksum")?;
+
+    let header = Header {
+        format,
+        kind,
+        params,
+        entries,
+        checksum,
+    };
+    Ok((header, &content[end + 1..]))
+}
+
+fn decode_body<T: Entry>(header: &Header, body: &[u8]) -> Result<Vec<T>, CacheError> {
+    if format!("{:016x}", checksum_of(body)) != header.checksum {
+        // A truncated text file is more helpfully reported by its line count
+        if header.format == Format::Text {
+            let found = body.iter().filter(|&&b| b == b'\n').count();
+            if found != header.entries {
+                return Err(CacheError::Count {
+                    expected: header.entries,
+                    found,
+                });
+            }
+        }
+        return Err(CacheError::Checksum);
+    }
+
+    let mut entries = Vec::with_capacity(header.entries);
+    match header.format {
+        Format::Text => {
+            let body = std::str::from_utf8(body).map_err(|_| CacheError::MalformedEntry(0))?;
+            for (i, line) in body.split_terminator('\n').enumerate() {
+                entries.push(T::from_text(line).ok_or(CacheError::MalformedEntry(i))?);
+            }
+        }
+        Format::Binary => {
+            let mut pos = 0;
+            while pos < body.len() {
+                let i = entries.len();
+                entries.push(T::decode(body, &mut pos).ok_or(CacheError::MalformedEntry(i))?);
+            }
+        }
+    }
+    if entries.len() != header.entries {
+        return Err(CacheError::Count {
+            expected: header.entries,
+            found: entries.len(),
+        });
+    }
+    Ok(entries)
+}
+
+/// Appends `x` to `buf` as LEB128 varint.
+pub fn write_varint(buf: &mut Vec<u8>, mut x: u64) {
+    while x >= 0x80 {
+        buf.push((x as u8) | 0x80);
+        x >>= 7;
+    }
+    buf.push(x as u8);
+}
+
+/// Reads a LEB128 varint from `bytes` at `pos` and advances `pos` past it.
+pub fn read_varint(bytes: &[u8], pos: &mut usize) -> Option<u64> {
+    let mut x = 0u64;
+    for shift in (0..64).step_by(7) {
+        let b = *bytes.get(*pos)?;
+        *pos += 1;
+        x |= u64::from(b & 0x7f) << shift;
+        if b & 0x80 == 0 {
+            return Some(x);
+        }
+    }
+    None
+}
+
+/// Appends a string of `'0'`s and `'1'`s to `buf`, as its length followed by
+/// the bits packed into bytes, most significant bit first.
+pub fn write_bits(buf: &mut Vec<u8>, bits: &str) {
+    write_varint(buf, bits.len() as u64);
+    for chunk in bits.as_bytes().chunks(8) {
+        let byte = chunk
+            .iter()
+            .enumerate()
+            .fold(0u8, |byte, (i, &c)| byte | (((c == b'1') as u8) << (7 - i)));
+        buf.push(byte);
+    }
+}
+
+/// Reads a string of `'0'`s and `'1'`s written by
+/// [`write_bits`](fn.write_bits.html) and advances `pos` past it.
+pub fn read_bits(bytes: &[u8], pos: &mut usize) -> Option<String> {
+    let len = read_varint(bytes, pos)? as usize;
+    let packed = bytes.get(*pos..*pos + len.div_ceil(8))?;
+    *pos += packed.len();
+    Some(
+        (0..len)
+            .map(|i| {
+                if packed[i / 8] & (1 << (7 - i % 8)) != 0 {
+                    '1'
+                } else {
+                    '0'
+                }
+            })
+            .collect(),
+    )
+}
+
+/// FNV-1a hash over the bytes.
+fn checksum_of(bytes: &[u8]) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325_u64;
+    for &byte in bytes {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
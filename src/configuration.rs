@@ -3,19 +3,24 @@
 use std::{
     fmt::{self, Display},
     ops::{Deref, RangeInclusive},
+    path::Path,
 };
 
 use std::error::Error;
 use std::fmt::Debug;
 
-use clap::{App, Arg};
+use clap::{App, Arg, ArgGroup, SubCommand};
 
 use lazy_static::lazy_static;
 use std::sync::{RwLock, RwLockReadGuard};
 
 use crate::{
-    polymorphism::{PolymorphismConfiguration, PolymorphismKind},
-    triad::Triad,
+    cache_file::Format,
+    consistency::Consistency,
+    identity::ParseIdentityError,
+    polymorphism::{primes, PolymorphismConfiguration, PolymorphismKind, ValueOrder},
+    search::{Backtracking, Parallelism, Restarts, VariableOrder},
+    triad::{ParseTriadError, Triad},
 };
 
 /// A set of options for tripolys
@@ -26,9 +31,15 @@ pub struct TripolysOptions {
     /// Range in which to look for core triads
     pub range: Option<RangeInclusive<u32>>,
 
+    /// Whether to consider all triads in the range, not only the core triads
+    pub all: bool,
+
     /// Triad to operate on
     pub triad: Option<Triad>,
 
+    /// File of a graph to operate on
+    pub graph: Option<String>,
+
     /// Name of the file from which the triads are read in
     pub list: Option<String>,
 
@@ -38,6 +49,51 @@ pub struct TripolysOptions {
     /// Polymorphism to check
     pub polymorphism_config: Option<PolymorphismConfiguration>,
 
+    /// Cyclic polymorphisms of the prime arities in a range to check
+    pub cyclic: Vec<PolymorphismConfiguration>,
+
+    /// Graph representation to search on
+    pub backend: Backend,
+
+    /// Consistencies established before and maintained during the search
+    pub consistency: Consistency,
+
+    /// How the search backtracks
+    pub backtracking: Backtracking,
+
+    /// When the search restarts
+    pub restarts: Restarts,
+
+    /// Seed of the random tie-breaking, the search is deterministic without
+    pub seed: Option<u64>,
+
+    /// Whether a single search runs in parallel
+    pub parallelism: Parallelism,
+
+    /// Order in which the search assigns the vertices of the indicator graph
+    pub variable_order: VariableOrder,
+
+    /// Value the search tries first for a tuple
+    pub value_order: ValueOrder,
+
+    /// Whether to count the solutions instead of finding one
+    pub count: bool,
+
+    /// Number of solutions after which counting stops
+    pub limit: Option<u64>,
+
+    /// Whether to count the solutions modulo the automorphisms of the template
+    pub modulo_automorphisms: bool,
+
+    /// Format to convert the cache files of the data directory to
+    pub convert: Option<Format>,
+
+    /// Whether to add headers to the cache files of earlier versions
+    pub import_legacy: bool,
+
+    /// Instance and template of a homomorphism problem
+    pub hom: Option<HomOptions>,
+
     /// How the program should run
     pub run: Run,
 }
@@ -46,26 +102,123 @@ pub struct TripolysOptions {
 pub enum OptionsError {
     /// The given range is empty
     EmptyRange,
+    /// Unable to parse range from argument
+    FlawedRange,
+    /// The given range contains no prime
+    NoPrimes,
     /// No polymorphism registered with that name
     PolymorphismNotFound,
     /// Unable to parse triad from argument
-    FlawedTriad,
+    FlawedTriad(ParseTriadError),
+    /// Unable to parse identities from argument
+    FlawedIdentities(ParseIdentityError),
+    /// Unable to parse the arity of a polymorphism from argument, which must
+    /// be at least the given number
+    FlawedArity(u32),
+    /// Unable to parse consistencies from argument
+    FlawedConsistency(&'static str),
+    /// Unable to parse backtracking from argument
+    FlawedBacktracking(&'static str),
+    /// Unable to parse restarts from argument
+    FlawedRestarts(&'static str),
+    /// Unable to parse seed from argument
+    FlawedSeed,
+    /// Unable to parse limit from argument
+    FlawedLimit,
+    /// Unable to parse parallelism from argument
+    FlawedParallelism(&'static str),
+    /// Unable to parse variable or value order from argument
+    FlawedOrder(&'static str),
+    /// A value order was given for the `hom` mode, whose vertices aren't tuples
+    HomValueOrder,
+    /// None of the inputs or modes was given
+    NoInput,
 }
 
 impl fmt::Display for OptionsError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match *self {
+        match self {
             OptionsError::EmptyRange => write!(f, "Range is empty"),
+            OptionsError::FlawedRange => {
+                write!(f, "Unable to parse range from argument, e.g. 5 or 3-6")
+            }
+            OptionsError::NoPrimes => write!(f, "Range contains no prime"),
             OptionsError::PolymorphismNotFound => {
                 write!(f, "No polymorphism registered with that name")
             }
-            OptionsError::FlawedTriad => write!(f, "Unable to parse triad from argument"),
+            OptionsError::FlawedTriad(e) => {
+                write!(f, "Unable to parse triad from argument: {}", e)
+            }
+            OptionsError::FlawedIdentities(e) => {
+                write!(f, "Unable to parse identities from argument: {}", e)
+            }
+            OptionsError::FlawedConsistency(e) => {
+                write!(f, "Unable to parse consistency from argument: {}", e)
+            }
+            OptionsError::FlawedBacktracking(e) => {
+                write!(f, "Unable to parse backtracking from argument: {}", e)
+            }
+            OptionsError::FlawedRestarts(e) => {
+                write!(f, "Unable to parse restarts from argument: {}", e)
+            }
+            OptionsError::FlawedArity(min) => {
+                write!(f, "Unable to parse arity from argument, it must be at least {}", min)
+            }
+            OptionsError::FlawedSeed => write!(f, "Unable to parse seed from argument"),
+            OptionsError::FlawedLimit => write!(f, "Unable to parse limit from argument"),
+            OptionsError::FlawedParallelism(e) => {
+                write!(f, "Unable to parse parallelism from argument: {}", e)
+            }
+            OptionsError::FlawedOrder(e) => {
+                write!(f, "Unable to parse order from argument: {}", e)
+            }
+            OptionsError::HomValueOrder => {
+                write!(f, "A value order only applies to polymorphisms, not to hom")
+            }
+            OptionsError::NoInput => write!(
+                f,
+                "You must provide one of the following arguments: triad, graph, length, \
+                 nodes, list, convert, or the hom subcommand"
+            ),
         }
     }
 }
 
 impl Error for OptionsError {}
 
+#[derive(Debug)]
+pub enum Backend {
+    /// Search on `AdjacencyList`
+    AdjacencyList,
+
+    /// Search on `DenseGraph`
+    Dense,
+
+    /// Search on an implicit `PowerGraph` of a `DenseGraph`
+    Implicit,
+}
+
+/// The options of the `hom` mode
+pub struct HomOptions {
+    /// File of the instance digraph G
+    pub instance: String,
+
+    /// Template digraph H
+    pub template: Template,
+
+    /// File with lists that restrict the images of the vertices of G
+    pub lists: Option<String>,
+}
+
+/// The template of a homomorphism problem
+pub enum Template {
+    /// A triad, with vertices labeled as in its `AdjacencyList`
+    Triad(Triad),
+
+    /// A graph file
+    File(String),
+}
+
 #[derive(Debug)]
 pub enum Run {
     /// Write triad to dot-format
@@ -76,6 +229,18 @@ pub enum Run {
 
     /// Check whether a given polymorphism exists
     Polymorphism,
+
+    /// Check whether cyclic polymorphisms of several prime arities exist
+    Cyclic,
+
+    /// Check whether arc consistency solves the CSP of a triad or graph
+    Width1,
+
+    /// Convert the cache files of the data directory
+    Convert,
+
+    /// Decide whether there is a homomorphism from one graph to another
+    Hom,
 }
 
 impl TripolysOptions {
@@ -102,6 +267,13 @@ impl TripolysOptions {
                     .value_name("NUM or RANGE")
                     .help("Maximum number of nodes of triads, e.g. 10 or 5-9"),
             )
+            .arg(
+                Arg::with_name("all")
+                    .short("a")
+                    .long("all")
+                    .conflicts_with_all(&["triad", "list"])
+                    .help("Consider all triads in the range, not only the core triads"),
+            )
             .arg(
                 Arg::with_name("triad")
                     .short("t")
@@ -111,32 +283,49 @@ impl TripolysOptions {
                     .value_name("TRIAD")
                     .help("Triad to operate on, e.g. 111,011,01"),
             )
+            .arg(
+                Arg::with_name("graph")
+                    .short("g")
+                    .long("graph")
+                    .conflicts_with_all(&["nodes", "length", "list"])
+                    .takes_value(true)
+                    .value_name("FILE")
+                    .help("Graph to operate on (edge list, dot, digraph6 or graph6)"),
+            )
+            .group(ArgGroup::with_name("input").args(&["triad", "graph"]))
             .arg(
                 Arg::with_name("idempotent")
                     .short("i")
                     .long("idempotent")
-                    .requires("polymorphism")
+                    .requires("kind")
                     .help("Whether the polymorphism should be idempotent"),
             )
             .arg(
                 Arg::with_name("conservative")
                     .short("c")
                     .long("conservative")
-                    .requires("polymorphism")
+                    .requires("kind")
                     .help("Whether the polymorphism should be conservative"),
             )
             .arg(
                 Arg::with_name("core")
                     .short("C")
                     .long("core")
-                    .requires("triad")
+                    .requires("input")
                     .help("Checks if triad is a core"),
             )
+            .arg(
+                Arg::with_name("width-1")
+                    .long("width-1")
+                    .requires("input")
+                    .conflicts_with("kind")
+                    .help("Decides whether arc consistency solves the CSP of the triad or graph"),
+            )
             .arg(
                 Arg::with_name("dot")
                     .short("D")
                     .long("dot")
-                    .requires("triad")
+                    .requires("input")
                     .value_name("NAME")
                     // .default_value("graph.dot")
                     .help("Write the graph to file (in dot format)")
@@ -147,9 +336,127 @@ impl TripolysOptions {
                     .short("p")
                     .long("polymorphism")
                     .value_name("NAME")
-                    .help("Polymorphism to check, e.g. commutative")
+                    .help(
+                        "Polymorphism to check, e.g. commutative, cyclic:5, nu:4, wnu:5, \
+                         wnu:3,4 or totally-symmetric:3, or height 1 identities it should \
+                         satisfy, e.g. 'f(x,x,y)=f(x,y,x)=f(y,x,x)'",
+                    )
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name("cyclic")
+                    .long("cyclic")
+                    .value_name("RANGE")
+                    .help("Check cyclic polymorphisms of each prime arity in RANGE, e.g. 2-7")
+                    .takes_value(true),
+            )
+            .group(ArgGroup::with_name("kind").args(&["polymorphism", "cyclic"]))
+            .arg(
+                Arg::with_name("backend")
+                    .short("b")
+                    .long("backend")
+                    .value_name("NAME")
+                    .possible_values(&["list", "dense", "implicit"])
+                    .default_value("list")
+                    .help("Graph representation to search on, e.g. dense")
                     .takes_value(true),
             )
+            .arg(
+                Arg::with_name("consistency")
+                    .long("consistency")
+                    .value_name("PRE[:PROP]")
+                    .default_value("ac2001:mac")
+                    .help(
+                        "Consistency before the search (none, ac1, ac3, ac2001, sac1, sac-opt) \
+                         and during it (fc, mac), e.g. sac-opt:mac",
+                    )
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name("backtracking")
+                    .long("backtracking")
+                    .value_name("BACKTRACKING")
+                    .default_value("chronological")
+                    .help(
+                        "Chronological backtracking or conflict-directed backjumping with \
+                         at most N learned nogoods (chronological, cbj, cbj:N)",
+                    )
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name("restarts")
+                    .long("restarts")
+                    .value_name("POLICY")
+                    .default_value("none")
+                    .help(
+                        "When the search restarts, after a number of backtracks that follows \
+                         the Luby sequence or grows geometrically, each run breaks ties anew \
+                         (none, luby[:BASE], geometric[:BASE[,FACTOR]])",
+                    )
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name("seed")
+                    .long("seed")
+                    .value_name("NUM")
+                    .help(
+                        "Break ties of the search randomly with the seed NUM, \
+                         otherwise the search is deterministic",
+                    )
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name("parallel")
+                    .long("parallel")
+                    .value_name("MODE")
+                    .default_value("none")
+                    .help(
+                        "Run a single search in parallel, split on the first DEPTH branching \
+                         vertices or as a portfolio of N racing searches \
+                         (none, split[:DEPTH], portfolio[:N])",
+                    )
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name("variable-order")
+                    .long("variable-order")
+                    .value_name("ORDER")
+                    .default_value("static")
+                    .possible_values(&["static", "dom", "dom-deg", "dom-wdeg", "level"])
+                    .help("Order in which the search assigns the vertices of the indicator graph")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name("value-order")
+                    .long("value-order")
+                    .value_name("ORDER")
+                    .default_value("index")
+                    .possible_values(&["index", "idempotent", "projection"])
+                    .help("Value the search tries first for a tuple")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name("count")
+                    .long("count")
+                    .help("Count the polymorphisms or homomorphisms instead of finding one"),
+            )
+            .arg(
+                Arg::with_name("limit")
+                    .long("limit")
+                    .value_name("NUM")
+                    .requires("count")
+                    .help("Stop counting after NUM solutions")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name("modulo-automorphisms")
+                    .long("modulo-automorphisms")
+                    .requires("count")
+                    .help(
+                        "Count only one of the solutions f and σ ∘ f, for each automorphism σ \
+                         of the template that preserves the lists",
+                    ),
+            )
             .arg(
                 Arg::with_name("list")
                     .short("L")
@@ -158,6 +465,23 @@ impl TripolysOptions {
                     .help("Check the polymorphism for the triads listed in FILE")
                     .takes_value(true),
             )
+            .arg(
+                Arg::with_name("convert")
+                    .long("convert")
+                    .value_name("FORMAT")
+                    .possible_values(&["text", "binary"])
+                    .help("Convert the cache files of the data directory, e.g. binary")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name("import-legacy")
+                    .long("import-legacy")
+                    .requires("convert")
+                    .help(
+                        "Add headers to the cache files without one when converting, \
+                         otherwise they are regenerated when needed",
+                    ),
+            )
             .arg(
                 Arg::with_name("data")
                     .short("d")
@@ -167,32 +491,79 @@ impl TripolysOptions {
                     .help("Where to store the data")
                     .takes_value(true), // .required(true)
             )
+            .subcommand(
+                SubCommand::with_name("hom")
+                    .about("Decides whether there is a homomorphism from INSTANCE to TEMPLATE")
+                    .arg(
+                        Arg::with_name("instance")
+                            .value_name("INSTANCE")
+                            .required(true)
+                            .help("File of the instance digraph (edge list or dot)"),
+                    )
+                    .arg(
+                        Arg::with_name("template")
+                            .value_name("TEMPLATE")
+                            .required(true)
+                            .help("Template triad, e.g. 111,011,01, or file of a digraph"),
+                    )
+                    .arg(
+                        Arg::with_name("lists")
+                            .short("L")
+                            .long("lists")
+                            .value_name("FILE")
+                            .help("Restrict the images of the vertices to the lists in FILE")
+                            .takes_value(true),
+                    ),
+            )
             .get_matches();
 
+        let hom = match args.subcommand_matches("hom") {
+            Some(hom) => {
+                // The template is read from a file only if it exists, otherwise
+                // it has to be a triad.
+                let template = hom.value_of("template").unwrap();
+                let template = match template.parse::<Triad>() {
+                    Ok(triad) => Template::Triad(triad),
+                    Err(_) if Path::new(template).exists() => Template::File(template.into()),
+                    Err(e) => return Err(OptionsError::FlawedTriad(e)),
+                };
+                Some(HomOptions {
+                    instance: hom.value_of("instance").unwrap().into(),
+                    template,
+                    lists: hom.value_of("lists").map(|s| s.into()),
+                })
+            }
+            None => None,
+        };
+
         if !args.is_present("triad")
+            && !args.is_present("graph")
             && !args.is_present("length")
             && !args.is_present("nodes")
             && !args.is_present("list")
+            && !args.is_present("convert")
+            && hom.is_none()
         {
-            panic!("You must provide exactly one of the following arguments: triad, length, nodes");
+            return Err(OptionsError::NoInput);
         }
 
         let nodes = args.value_of("nodes").map(|s| s.to_string());
         let length = args.value_of("length").map(|s| s.to_string());
         let list = args.value_of("list").map(|s| s.to_string());
 
+        let all = args.is_present("all");
         let conservative = args.is_present("conservative");
         let idempotent = args.is_present("idempotent");
 
         let triad = if let Some(s) = args.value_of("triad") {
-            if let Ok(triad) = s.parse::<Triad>() {
-                Some(triad)
-            } else {
-                return Err(OptionsError::FlawedTriad);
+            match s.parse::<Triad>() {
+                Ok(triad) => Some(triad),
+                Err(e) => return Err(OptionsError::FlawedTriad(e)),
             }
         } else {
             None
         };
+        let graph = args.value_of("graph").map(|v| v.into());
         let dot = args.value_of("dot").map(|v| v.into());
         let polymorphism = if let Some(p) = args.value_of("polymorphism") {
             Some(PolymorphismConfiguration::new(
@@ -203,11 +574,84 @@ impl TripolysOptions {
         } else {
             None
         };
+        let cyclic = match args.value_of("cyclic") {
+            Some(s) => {
+                let configs = primes(parse_range(s)?)
+                    .map(|p| {
+                        let kind = PolymorphismKind::Cyclic(p);
+                        PolymorphismConfiguration::new(kind, conservative, idempotent)
+                    })
+                    .collect::<Vec<_>>();
+                if configs.is_empty() {
+                    return Err(OptionsError::NoPrimes);
+                }
+                configs
+            }
+            None => Vec::new(),
+        };
+
+        let backend = match args.value_of("backend") {
+            Some("dense") => Backend::Dense,
+            Some("implicit") => Backend::Implicit,
+            _ => Backend::AdjacencyList,
+        };
 
-        let run = if args.is_present("dot") {
+        let consistency = match args.value_of("consistency").unwrap().parse() {
+            Ok(consistency) => consistency,
+            Err(e) => return Err(OptionsError::FlawedConsistency(e)),
+        };
+        let backtracking = match args.value_of("backtracking").unwrap().parse() {
+            Ok(backtracking) => backtracking,
+            Err(e) => return Err(OptionsError::FlawedBacktracking(e)),
+        };
+        let restarts = match args.value_of("restarts").unwrap().parse() {
+            Ok(restarts) => restarts,
+            Err(e) => return Err(OptionsError::FlawedRestarts(e)),
+        };
+        let seed = match args.value_of("seed").map(str::parse) {
+            Some(Ok(seed)) => Some(seed),
+            Some(Err(_)) => return Err(OptionsError::FlawedSeed),
+            None => None,
+        };
+        let parallelism = match args.value_of("parallel").unwrap().parse() {
+            Ok(parallelism) => parallelism,
+            Err(e) => return Err(OptionsError::FlawedParallelism(e)),
+        };
+        let variable_order = match args.value_of("variable-order").unwrap().parse() {
+            Ok(order) => order,
+            Err(e) => return Err(OptionsError::FlawedOrder(e)),
+        };
+        let value_order = match args.value_of("value-order").unwrap().parse() {
+            Ok(order) => order,
+            Err(e) => return Err(OptionsError::FlawedOrder(e)),
+        };
+        if hom.is_some() && value_order != ValueOrder::Index {
+            return Err(OptionsError::HomValueOrder);
+        }
+
+        let count = args.is_present("count");
+        let limit = match args.value_of("limit").map(str::parse) {
+            Some(Ok(limit)) => Some(limit),
+            Some(Err(_)) => return Err(OptionsError::FlawedLimit),
+            None => None,
+        };
+        let modulo_automorphisms = args.is_present("modulo-automorphisms");
+
+        let convert = args.value_of("convert").map(|s| s.parse().unwrap());
+        let import_legacy = args.is_present("import-legacy");
+
+        let run = if hom.is_some() {
+            Run::Hom
+        } else if convert.is_some() {
+            Run::Convert
+        } else if args.is_present("dot") {
             Run::Dot
         } else if args.is_present("core") {
             Run::Core
+        } else if args.is_present("width-1") {
+            Run::Width1
+        } else if !cyclic.is_empty() {
+            Run::Cyclic
         } else {
             Run::Polymorphism
         };
@@ -219,22 +663,39 @@ impl TripolysOptions {
         } else {
             None
         };
-        let range = if let Some(s) = nodes.as_ref().or_else(|| length.as_ref()) {
+        let range = if let Some(s) = nodes.as_ref().or(length.as_ref()) {
             Some(parse_range(s)?)
         } else {
             None
         };
 
         let data = args.value_of("data").unwrap_or("data").to_string();
-        Globals::set(Globals { data });
+        Globals::set(Globals::new(&data));
 
         Ok(TripolysOptions {
             constraint,
             range,
+            all,
             triad,
+            graph,
             list,
             dot,
             polymorphism_config: polymorphism,
+            cyclic,
+            backend,
+            consistency,
+            backtracking,
+            restarts,
+            seed,
+            parallelism,
+            variable_order,
+            value_order,
+            count,
+            limit,
+            modulo_automorphisms,
+            convert,
+            import_legacy,
+            hom,
             // conservative,
             // idempotent,
             run,
@@ -269,17 +730,23 @@ impl Constraint {
 #[derive(Default)]
 pub struct Globals {
     pub data: String,
+    /// Format in which cache files are written
+    pub format: Format,
 }
 
 lazy_static! {
     static ref GLOBALS: RwLock<Option<Globals>> = RwLock::new(Some(Globals {
-        data: String::new()
+        data: String::new(),
+        format: Format::Text,
     }));
 }
 
 impl Globals {
     pub fn new(data: &str) -> Self {
-        Globals { data: data.into() }
+        Globals {
+            data: data.into(),
+            format: Format::of(data),
+        }
     }
 
     pub fn get() -> impl Deref<Target = Globals> {
@@ -299,10 +766,15 @@ impl Globals {
 }
 
 fn parse_range(s: &str) -> Result<RangeInclusive<u32>, OptionsError> {
-    let v = s.split('-').collect::<Vec<_>>();
-    let begin = v.get(0).unwrap().parse::<u32>().unwrap();
-    let end = v.get(1).map_or(begin, |s| s.parse::<u32>().unwrap());
-    let r = begin..=end;
+    let bound = |s: &str| s.parse::<u32>().map_err(|_| OptionsError::FlawedRange);
+    let r = match s.split('-').collect::<Vec<_>>()[..] {
+        [n] => {
+            let n = bound(n)?;
+            n..=n
+        }
+        [begin, end] => bound(begin)?..=bound(end)?,
+        _ => return Err(OptionsError::FlawedRange),
+    };
     if r.is_empty() {
         Err(OptionsError::EmptyRange)
     } else {
@@ -320,7 +792,28 @@ impl PolymorphismRegistry {
             "siggers" => Ok(PolymorphismKind::Siggers),
             "3/4wnu" => Ok(PolymorphismKind::WNU34),
             "3wnu" => Ok(PolymorphismKind::WNU3),
+            s if s.starts_with("cyclic:") => Ok(PolymorphismKind::Cyclic(arity(&s[7..], 2)?)),
+            s if s.starts_with("totally-symmetric:") => {
+                Ok(PolymorphismKind::TotallySymmetric(arity(&s[18..], 2)?))
+            }
+            s if s.starts_with("nu:") => Ok(PolymorphismKind::NU(arity(&s[3..], 3)?)),
+            s if s.starts_with("wnu:") => match s[4..].split_once(',') {
+                Some((k, l)) => Ok(PolymorphismKind::WNUPair(arity(k, 3)?, arity(l, 3)?)),
+                None => Ok(PolymorphismKind::WNU(arity(&s[4..], 3)?)),
+            },
+            s if s.contains('(') => match s.parse() {
+                Ok(identities) => Ok(PolymorphismKind::Identities(identities)),
+                Err(e) => Err(OptionsError::FlawedIdentities(e)),
+            },
             &_ => Err(OptionsError::PolymorphismNotFound),
         }
     }
 }
+
+// Parses the arity `s` of a polymorphism, which must be at least `min`.
+fn arity(s: &str, min: u32) -> Result<u32, OptionsError> {
+    match s.parse() {
+        Ok(k) if k >= min => Ok(k),
+        _ => Err(OptionsError::FlawedArity(min)),
+    }
+}
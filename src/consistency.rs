@@ -1,24 +1,33 @@
-//! A collection of various local-consistency algorithms such as AC-3 and
-//! SAC-Opt implemented to work on graphs.
-use std::fmt::Debug;
+//! A collection of various local-consistency algorithms such as AC-3, AC-2001,
+//! SAC-Opt and PC-2 implemented to work on graphs.
+use std::fmt::{self, Debug, Display};
 use std::iter::FromIterator;
+use std::marker::PhantomData;
+use std::str::FromStr;
 use std::time::Instant;
-use std::{collections::HashMap, collections::HashSet, hash::Hash};
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::hash::Hash;
 
-use crate::adjacency_list::VertexID;
-use crate::adjacency_list::{AdjacencyList, Set};
+use crate::adjacency_list::{DenseID, VertexID};
+use crate::adjacency_list::Set;
+use crate::graph::Graph;
 use crate::metrics::Metrics;
+use crate::search::Search;
 
 /// Abstraction of a local consistency algorithm that takes two graphs and a
 /// list and tries to make the list consistent. Returns None, if the list
 /// can not be made consistent, otherwise the consistent lists is returned.
-pub trait LocalConsistency<V0: Eq + Clone + Hash, V1: Eq + Clone + Hash>:
-    Fn(&AdjacencyList<V0>, &AdjacencyList<V1>, Lists<V0, V1>) -> Option<Lists<V0, V1>>
+pub trait LocalConsistency<G0: Graph, G1: Graph>:
+    Fn(&G0, &G1, Lists<G0::Vertex, G1::Vertex>) -> Option<Lists<G0::Vertex, G1::Vertex>>
+where
+    G1::Vertex: DenseID,
 {
 }
 
-impl<V0: Eq + Clone + Hash, V1: Eq + Clone + Hash, F> LocalConsistency<V0, V1> for F where
-    F: Fn(&AdjacencyList<V0>, &AdjacencyList<V1>, Lists<V0, V1>) -> Option<Lists<V0, V1>>
+impl<G0: Graph, G1: Graph, F> LocalConsistency<G0, G1> for F
+where
+    G1::Vertex: DenseID,
+    F: Fn(&G0, &G1, Lists<G0::Vertex, G1::Vertex>) -> Option<Lists<G0::Vertex, G1::Vertex>>,
 {
 }
 
@@ -28,7 +37,7 @@ impl<V0: Eq + Clone + Hash, V1: Eq + Clone + Hash, F> LocalConsistency<V0, V1> f
 #[macro_export]
 macro_rules! list {
     ($($v:expr),* $(,)?) => {
-        std::iter::Iterator::collect(std::array::IntoIter::new([$($v,)*]))
+        std::iter::Iterator::collect(std::iter::IntoIterator::into_iter([$($v,)*]))
     };
 }
 
@@ -40,14 +49,16 @@ macro_rules! list {
 ///
 /// Returns None, if an empty list is derived for some vertex v, otherwise
 /// arc-consistent lists are returned.
-pub fn ac_1_lists<V0, V1>(
-    g0: &AdjacencyList<V0>,
-    g1: &AdjacencyList<V1>,
+pub fn ac_1_lists<V0, V1, G0, G1>(
+    g0: &G0,
+    g1: &G1,
     mut lists: Lists<V0, V1>,
 ) -> Option<Lists<V0, V1>>
 where
+    G0: Graph<Vertex = V0>,
+    G1: Graph<Vertex = V1>,
     V0: VertexID,
-    V1: VertexID,
+    V1: DenseID,
 {
     for v0 in g0.vertices() {
         if !lists.contains_variable(v0) {
@@ -64,14 +75,14 @@ where
             for u1 in lists.get(&u0).unwrap().clone().iter() {
                 let mut is_possible = false;
                 for v1 in lists.get(&v0).unwrap().iter() {
-                    if g1.has_edge(u1, v1) {
+                    if g1.has_edge(&u1, &v1) {
                         is_possible = true;
                         break;
                     }
                 }
 
                 if !is_possible {
-                    lists.get_mut(&u0).unwrap().remove(u1);
+                    lists.get_mut(&u0).unwrap().remove(&u1);
                     if lists.get(&u0).unwrap().is_empty() {
                         return None;
                     }
@@ -82,14 +93,14 @@ where
             for v1 in lists.get(&v0).unwrap().clone().iter() {
                 let mut is_possible = false;
                 for u1 in lists.get(&u0).unwrap().iter() {
-                    if g1.has_edge(u1, v1) {
+                    if g1.has_edge(&u1, &v1) {
                         is_possible = true;
                         break;
                     }
                 }
 
                 if !is_possible {
-                    lists.get_mut(&v0).unwrap().remove(v1);
+                    lists.get_mut(&v0).unwrap().remove(&v1);
                     if lists.get(&v0).unwrap().is_empty() {
                         return None;
                     }
@@ -104,10 +115,12 @@ where
 
 /// A modification of `ac1_lists` that is initialized with a list of all nodes
 /// of g1 for each node in g0.
-pub fn ac_1<V0, V1>(g0: &AdjacencyList<V0>, g1: &AdjacencyList<V1>) -> Option<Lists<V0, V1>>
+pub fn ac_1<V0, V1, G0, G1>(g0: &G0, g1: &G1) -> Option<Lists<V0, V1>>
 where
+    G0: Graph<Vertex = V0>,
+    G1: Graph<Vertex = V1>,
     V0: VertexID,
-    V1: VertexID,
+    V1: DenseID,
 {
     ac_1_lists(g0, g1, Lists::new())
 }
@@ -120,35 +133,19 @@ where
 ///
 /// Returns None, if an empty list is derived for some vertex v, otherwise an
 /// arc-consistent map is returned.
-pub fn ac_3_lists<V0, V1>(
-    g0: &AdjacencyList<V0>,
-    g1: &AdjacencyList<V1>,
-    f: Lists<V0, V1>,
-) -> Option<Lists<V0, V1>>
-where
-    V0: VertexID + Debug,
-    V1: VertexID + Debug,
-{
-    ac_3_lists_removed(g0, g1, f).map(|(a, _)| a)
-}
-
-/// Implementation of the AC-3 algorithm due to Mackworth 1977, specialized to
-/// find graph homomorphisms.
 ///
-/// f represents a list of vertices for each vertex of g0. If there's no list
-/// specified for a vertex v, a list of all nodes of g1 is assigned to v.
-///
-/// Returns None, if an empty list is derived for some vertex v, otherwise (a,
-/// b) is returned where a is an arc-consistent map and b the sets of removed
-/// vertices for each vertex.
-fn ac_3_lists_removed<V0, V1>(
-    g0: &AdjacencyList<V0>,
-    g1: &AdjacencyList<V1>,
+/// The arcs are revised in a fixed order of the vertices of g0, independent of
+/// the order in which `g0` stores them.
+pub fn ac_3_lists<V0, V1, G0, G1>(
+    g0: &G0,
+    g1: &G1,
     mut lists: Lists<V0, V1>,
-) -> Option<(Lists<V0, V1>, Lists<V0, V1>)>
+) -> Option<Lists<V0, V1>>
 where
+    G0: Graph<Vertex = V0>,
+    G1: Graph<Vertex = V1>,
     V0: VertexID + Debug,
-    V1: VertexID + Debug,
+    V1: DenseID + Debug,
 {
     for v0 in g0.vertices() {
         if !lists.contains_variable(v0) {
@@ -157,7 +154,7 @@ where
     }
 
     let edges = g0.edges();
-    let mut pending_list = HashSet::<(V0, V0, bool)>::new();
+    let mut pending_list = BTreeSet::<(V0, V0, bool)>::new();
 
     for (u0, v0) in edges {
         pending_list.insert((u0.clone(), v0.clone(), false));
@@ -172,24 +169,12 @@ where
         items.insert(v0.clone(), Vec::<(V0, V0, bool)>::new());
     }
 
-    for (u0, v0, dir) in pending_list.iter().cloned() {
-        items.get_mut(&v0).unwrap().push((u0, v0, dir));
+    for (u0, v0, dir) in &pending_list {
+        items.get_mut(v0).unwrap().push((u0.clone(), v0.clone(), *dir));
     }
 
-    let mut removed = Lists::<V0, V1>::new();
-
-    while !pending_list.is_empty() {
-        let (u0, v0, dir) = pending_list.iter().cloned().next().unwrap();
-        pending_list.remove(&(u0.clone(), v0.clone(), dir));
-
-        if let Some(rem) = arc_reduce(&u0, &v0, dir, &mut lists, g1) {
-            for (v, list_v) in rem {
-                if removed.contains_variable(&v) {
-                    removed.get_mut(&v).unwrap().merge(&list_v);
-                } else {
-                    removed.insert(v, list_v);
-                }
-            }
+    while let Some((u0, v0, dir)) = pending_list.pop_first() {
+        if arc_reduce(&u0, &v0, dir, &mut lists, g1) {
             // list of x changed, was the empty list derived?
             if lists.get(&u0).unwrap().is_empty() {
                 return None;
@@ -199,44 +184,40 @@ where
             }
         }
     }
-    Some((lists, removed))
+    Some(lists)
 }
 
 /// A modification of `ac3_lists` that is initialized with a list of all nodes
 /// of g1 for each node in g0.
-pub fn ac_3<V0, V1>(g0: &AdjacencyList<V0>, g1: &AdjacencyList<V1>) -> Option<Lists<V0, V1>>
+pub fn ac_3<V0, V1, G0, G1>(g0: &G0, g1: &G1) -> Option<Lists<V0, V1>>
 where
+    G0: Graph<Vertex = V0>,
+    G1: Graph<Vertex = V1>,
     V0: VertexID + Debug,
-    V1: VertexID + Debug,
+    V1: DenseID + Debug,
 {
     ac_3_lists(g0, g1, Lists::new())
 }
 
-// Implementation of the arc-reduce operation from ac3.  Returns None, if the
-// list of x was not reduced, otherwise the removed elements are returned.
-fn arc_reduce<V0, V1>(
-    u0: &V0,
-    v0: &V0,
-    dir: bool,
-    f: &mut Lists<V0, V1>,
-    g1: &AdjacencyList<V1>,
-) -> Option<Lists<V0, V1>>
+// Implementation of the arc-reduce operation from ac3. Returns true, if the
+// list of x was reduced, false otherwise.
+fn arc_reduce<V0, V1, G1>(u0: &V0, v0: &V0, dir: bool, f: &mut Lists<V0, V1>, g1: &G1) -> bool
 where
+    G1: Graph<Vertex = V1>,
     V0: VertexID + Debug,
-    V1: VertexID + Debug,
+    V1: DenseID + Debug,
 {
     let mut changed = false;
-    let mut removed = Lists::<V0, V1>::new();
     for u1 in f.get(u0).unwrap().clone().iter() {
         let mut is_possible = false;
         for v1 in f.get(v0).unwrap().iter() {
             if dir {
-                if g1.has_edge(v1, u1) {
+                if g1.has_edge(&v1, &u1) {
                     is_possible = true;
                     break;
                 }
             } else {
-                if g1.has_edge(u1, v1) {
+                if g1.has_edge(&u1, &v1) {
                     is_possible = true;
                     break;
                 }
@@ -244,20 +225,143 @@ where
         }
 
         if !is_possible {
-            f.get_mut(u0).unwrap().remove(u1);
-            if removed.contains_variable(u0) {
-                removed.get_mut(u0).unwrap().insert(u1.clone());
+            f.get_mut(u0).unwrap().remove(&u1);
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// Implementation of the AC-2001 algorithm due to Bessiere and Régin 2001,
+/// specialized to find graph homomorphisms.
+///
+/// For each arc and each vertex in the list of its source, the last support
+/// found in the list of its target is remembered. As lists only shrink, a
+/// revision resumes the search for a support from there instead of rescanning
+/// the whole list, which gives the optimal running time of O(|E(g0)|·|V(g1)|²).
+/// Arcs are revised in first-in-first-out order.
+///
+/// f represents a list of vertices for each vertex of g0. If there's no list
+/// specified for a vertex v, a list of all nodes of g1 is assigned to v.
+///
+/// Returns None, if an empty list is derived for some vertex v, otherwise
+/// arc-consistent lists are returned.
+pub fn ac_2001_lists<V0, V1, G0, G1>(
+    g0: &G0,
+    g1: &G1,
+    mut lists: Lists<V0, V1>,
+) -> Option<Lists<V0, V1>>
+where
+    G0: Graph<Vertex = V0>,
+    G1: Graph<Vertex = V1>,
+    V0: VertexID + Debug,
+    V1: DenseID + Debug,
+{
+    let vars = g0.vertices().cloned().collect::<Vec<_>>();
+    let vals = g1.vertices().cloned().collect::<Vec<_>>();
+    let var_index = vars.iter().enumerate().map(|(i, v)| (v, i)).collect::<HashMap<_, _>>();
+    let val_index = vals.iter().enumerate().map(|(i, v)| (v, i)).collect::<HashMap<_, _>>();
+    let neighbors = |f: &dyn Fn(&V1) -> Vec<V1>| {
+        vals.iter()
+            .map(|a| f(a).iter().map(|b| val_index[b]).collect::<Vec<_>>())
+            .collect::<Vec<_>>()
+    };
+    let succs = neighbors(&|a| g1.out_neighbors(a).collect());
+    let preds = neighbors(&|a| g1.in_neighbors(a).collect());
+
+    let mut domains = vars
+        .iter()
+        .map(|x| match lists.get(x) {
+            Some(list) => vals.iter().map(|a| list.contains(a)).collect(),
+            None => vec![true; vals.len()],
+        })
+        .collect::<Vec<_>>();
+    let mut sizes = domains
+        .iter()
+        .map(|d| d.iter().filter(|&&a| a).count())
+        .collect::<Vec<_>>();
+    if sizes.contains(&0) {
+        return None;
+    }
+
+    // An arc (x, y, true) requires a successor in the list of y for every
+    // vertex in the list of x, an arc (x, y, false) a predecessor
+    let mut arcs = Vec::<(usize, usize, bool)>::new();
+    for (u0, v0) in g0.edges() {
+        let (x, y) = (var_index[&u0], var_index[&v0]);
+        arcs.push((x, y, true));
+        arcs.push((y, x, false));
+    }
+
+    // arcs to be revised, if the list of the respective vertex changed
+    let mut items = vec![Vec::new(); vars.len()];
+    for (i, &(_, y, _)) in arcs.iter().enumerate() {
+        items[y].push(i);
+    }
+
+    let mut last = vec![vec![0; vals.len()]; arcs.len()];
+    let mut pending = vec![true; arcs.len()];
+    let mut pending_list = (0..arcs.len()).collect::<VecDeque<_>>();
+
+    while let Some(i) = pending_list.pop_front() {
+        pending[i] = false;
+        let (x, y, forward) = arcs[i];
+        let neighbors = if forward { &succs } else { &preds };
+
+        let mut changed = false;
+        for a in 0..vals.len() {
+            if !domains[x][a] {
+                continue;
+            }
+            let mut support = last[i][a];
+            while support < neighbors[a].len() && !domains[y][neighbors[a][support]] {
+                support += 1;
+            }
+            if support < neighbors[a].len() {
+                last[i][a] = support;
             } else {
-                removed.insert(u0.clone(), list![u1.clone()]);
+                domains[x][a] = false;
+                sizes[x] -= 1;
+                changed = true;
+            }
+        }
+
+        if changed {
+            // list of x changed, was the empty list derived?
+            if sizes[x] == 0 {
+                return None;
+            }
+            for &j in &items[x] {
+                if !pending[j] {
+                    pending[j] = true;
+                    pending_list.push_back(j);
+                }
             }
-            changed = true;
         }
     }
-    if changed {
-        Some(removed)
-    } else {
-        None
+
+    for (x, domain) in vars.into_iter().zip(domains) {
+        let list = domain
+            .iter()
+            .zip(&vals)
+            .filter(|(&a, _)| a)
+            .map(|(_, v)| v.clone())
+            .collect();
+        lists.insert(x, list);
     }
+    Some(lists)
+}
+
+/// A modification of `ac_2001_lists` that is initialized with a list of all
+/// nodes of g1 for each node in g0.
+pub fn ac_2001<V0, V1, G0, G1>(g0: &G0, g1: &G1) -> Option<Lists<V0, V1>>
+where
+    G0: Graph<Vertex = V0>,
+    G1: Graph<Vertex = V1>,
+    V0: VertexID + Debug,
+    V1: DenseID + Debug,
+{
+    ac_2001_lists(g0, g1, Lists::new())
 }
 
 /// Implementation of the SAC-1 algorithm due to Bessiere and Debruyne 1997,
@@ -268,25 +372,45 @@ where
 ///
 /// Returns None, if an empty list is derived for some vertex v, otherwise
 /// singleton-arc-consistent lists are returned.
-pub fn sac_1_lists<V0, V1>(
-    g0: &AdjacencyList<V0>,
-    g1: &AdjacencyList<V1>,
+pub fn sac_1_lists<V0, V1, G0, G1>(
+    g0: &G0,
+    g1: &G1,
     lists: Lists<V0, V1>,
 ) -> Option<Lists<V0, V1>>
 where
+    G0: Graph<Vertex = V0>,
+    G1: Graph<Vertex = V1>,
     V0: VertexID + Debug,
-    V1: VertexID + Debug,
+    V1: DenseID + Debug,
 {
-    let mut lists = match ac_3_lists(g0, g1, lists) {
-        Some(v) => v,
-        None => return None,
-    };
+    sac_1_lists_with(g0, g1, lists, &ac_2001_lists)
+}
+
+/// A modification of `sac_1_lists` that establishes arc consistency with the
+/// algorithm `consistency`, e.g. `ac_3_lists` or `ac_2001_lists`.
+pub fn sac_1_lists_with<V0, V1, G0, G1, C>(
+    g0: &G0,
+    g1: &G1,
+    lists: Lists<V0, V1>,
+    consistency: &C,
+) -> Option<Lists<V0, V1>>
+where
+    G0: Graph<Vertex = V0>,
+    G1: Graph<Vertex = V1>,
+    V0: VertexID + Debug,
+    V1: DenseID + Debug,
+    C: LocalConsistency<G0, G1>,
+{
+    let mut lists = consistency(g0, g1, lists)?;
 
     let mut changed = true;
     while changed {
         changed = false;
 
-        for (k, v) in lists.clone().iter() {
+        // in a fixed order of the vertices of g0
+        let mut snapshot = lists.clone().into_iter().collect::<Vec<_>>();
+        snapshot.sort_by(|(k0, _), (k1, _)| k0.cmp(k1));
+        for (k, v) in &snapshot {
             for u in v.iter() {
                 let mut list = List::new();
                 list.insert(u.clone());
@@ -294,9 +418,9 @@ where
                 let mut lists_copy = lists.clone();
                 lists_copy.insert(k.clone(), list);
 
-                if ac_3_lists(g0, g1, lists_copy).is_none() {
+                if consistency(g0, g1, lists_copy).is_none() {
                     let mut v_clone = v.clone();
-                    v_clone.remove(u);
+                    v_clone.remove(&u);
                     lists.insert(k.clone(), v_clone);
                     changed = true;
                 };
@@ -311,156 +435,525 @@ where
 
 /// A modification of `sac1_lists` that is initialized with a list of all nodes
 /// of g1 for each node in g0.
-pub fn sac_1<V0, V1>(g0: &AdjacencyList<V0>, g1: &AdjacencyList<V1>) -> Option<Lists<V0, V1>>
+pub fn sac_1<V0, V1, G0, G1>(g0: &G0, g1: &G1) -> Option<Lists<V0, V1>>
 where
+    G0: Graph<Vertex = V0>,
+    G1: Graph<Vertex = V1>,
     V0: VertexID + Debug,
-    V1: VertexID + Debug,
+    V1: DenseID + Debug,
 {
     sac_1_lists(g0, g1, Lists::new())
 }
 
 /// Performs a depth-first-search to find a mapping from `g0` to `g1` that is
-/// locally consistent. The type of local consistency is determined by the
-/// algorithm `consistency`.
-pub fn backtrack_search_lists<V0, V1>(
-    g0: &AdjacencyList<V0>,
-    g1: &AdjacencyList<V1>,
+/// locally consistent. The type of local consistency is arc consistency,
+/// established by `ac_2001_lists`.
+pub fn backtrack_search_lists<V0, V1, G0, G1>(
+    g0: &G0,
+    g1: &G1,
+    lists: Lists<V0, V1>,
+    metrics: &mut Metrics,
+) -> Option<Lists<V0, V1>>
+where
+    G0: Graph<Vertex = V0>,
+    G1: Graph<Vertex = V1>,
+    V0: VertexID + Debug,
+    V1: DenseID + Debug,
+{
+    backtrack_search_lists_with(g0, g1, lists, &ac_2001_lists, Propagation::MAC, metrics)
+}
+
+/// Performs a depth-first-search to find a mapping from `g0` to `g1` that is
+/// locally consistent. The lists are first made consistent by the algorithm
+/// `consistency`, afterwards the local consistency `propagation` is maintained
+/// incrementally by a `Search`, which undoes its removals on backtracking.
+pub fn backtrack_search_lists_with<V0, V1, G0, G1, C>(
+    g0: &G0,
+    g1: &G1,
     lists: Lists<V0, V1>,
+    consistency: &C,
+    propagation: Propagation,
     metrics: &mut Metrics,
 ) -> Option<Lists<V0, V1>>
 where
+    G0: Graph<Vertex = V0>,
+    G1: Graph<Vertex = V1>,
     V0: VertexID + Debug,
-    V1: VertexID + Debug,
+    V1: DenseID + Debug,
+    C: LocalConsistency<G0, G1>,
 {
     let ac_start = Instant::now();
-    let res = ac_3_lists(g0, g1, lists);
+    let res = consistency(g0, g1, lists);
     metrics.ac_time = ac_start.elapsed();
-    let mut lists = res?;
-
-    // Sort vertices by their respective list length
-    let mut sorted_list = lists.clone().into_iter().collect::<Vec<_>>();
-    sorted_list.sort_by(|(_, l0), (_, l1)| l1.size().cmp(&l0.size()));
-    let mut vertex_list = sorted_list.iter().map(|(a, _)| a).collect::<Vec<_>>();
-
-    let mut backtracked = 0;
-    let mut removed = Vec::<(&V0, Lists<V0, V1>)>::new();
-    let mut set = Vec::<(V0, List<V1>)>::new();
-
-    let search_start = Instant::now();
-    let mut found = true;
-    while !vertex_list.is_empty() {
-        let v = vertex_list.pop().unwrap();
-        let list_v = lists.get_mut(v).unwrap();
-
-        if let Some(elem) = list_v.pop() {
-            set.push((v.clone(), lists.get(v).unwrap().clone()));
-            lists.insert(v.clone(), list![elem.clone()]);
-
-            if let Some((res, rem)) = ac_3_lists_removed(g0, g1, lists.clone()) {
-                removed.push((v, rem));
-                lists = res;
-            } else {
-                let (a, b) = set.pop().unwrap();
-                lists.insert(a, b);
-                vertex_list.push(v);
-            }
-        } else if let Some((w, list_w)) = removed.pop() {
-            lists.merge(&list_w);
-            let (u, list_u) = set.pop().unwrap();
-            lists.insert(u, list_u);
-            backtracked += 1;
-            vertex_list.push(v);
-            vertex_list.push(w);
-        } else {
-            found = false;
-            break;
+    let lists = res?;
+
+    Search::new(g0, g1, &lists)
+        .propagation(propagation)
+        .run(metrics)
+}
+
+/// The local consistency that is established before a backtracking search.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Preprocessing {
+    /// The lists are left as they are
+    None,
+    /// `ac_1_lists`
+    AC1,
+    /// `ac_3_lists`
+    AC3,
+    /// `ac_2001_lists`
+    AC2001,
+    /// `sac_1_lists`
+    SAC1,
+    /// `sac_opt_lists`
+    SACOpt,
+}
+
+impl Preprocessing {
+    /// Makes the lists consistent with the respective algorithm. Returns None,
+    /// if an empty list is derived for some vertex v.
+    pub fn apply<V0, V1, G0, G1>(
+        &self,
+        g0: &G0,
+        g1: &G1,
+        lists: Lists<V0, V1>,
+    ) -> Option<Lists<V0, V1>>
+    where
+        G0: Graph<Vertex = V0>,
+        G1: Graph<Vertex = V1>,
+        V0: VertexID + Debug,
+        V1: DenseID + Debug,
+    {
+        match self {
+            Preprocessing::None => Some(lists),
+            Preprocessing::AC1 => ac_1_lists(g0, g1, lists),
+            Preprocessing::AC3 => ac_3_lists(g0, g1, lists),
+            Preprocessing::AC2001 => ac_2001_lists(g0, g1, lists),
+            Preprocessing::SAC1 => sac_1_lists(g0, g1, lists),
+            Preprocessing::SACOpt => sac_opt_lists(g0, g1, lists),
+        }
+    }
+}
+
+/// The local consistency that is maintained during a backtracking search.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Propagation {
+    /// Forward checking, i.e. after an assignment only the lists of the
+    /// neighbours of the assigned vertex are revised
+    ForwardChecking,
+    /// Maintaining arc consistency, i.e. the removals of an assignment are
+    /// propagated until the lists are arc-consistent
+    MAC,
+}
+
+/// The consistencies of a backtracking search, written as `PRE:PROP`, e.g.
+/// `sac-opt:mac`. The propagation can be omitted and defaults to `mac`.
+///
+/// # Examples
+///
+/// ```
+/// use tripolys::consistency::{Consistency, Preprocessing, Propagation};
+///
+/// let consistency = "sac-opt:fc".parse::<Consistency>().unwrap();
+/// assert_eq!(consistency.preprocessing, Preprocessing::SACOpt);
+/// assert_eq!(consistency.propagation, Propagation::ForwardChecking);
+/// assert_eq!("ac3".parse::<Consistency>().unwrap().to_string(), "ac3:mac");
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Consistency {
+    pub preprocessing: Preprocessing,
+    pub propagation: Propagation,
+}
+
+impl Default for Consistency {
+    fn default() -> Self {
+        Consistency {
+            preprocessing: Preprocessing::AC2001,
+            propagation: Propagation::MAC,
         }
     }
-    metrics.search_time = search_start.elapsed();
-    metrics.backtracked = backtracked;
-    if found {
-        Some(lists)
-    } else {
-        None
+}
+
+impl Display for Consistency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let preprocessing = match self.preprocessing {
+            Preprocessing::None => "none",
+            Preprocessing::AC1 => "ac1",
+            Preprocessing::AC3 => "ac3",
+            Preprocessing::AC2001 => "ac2001",
+            Preprocessing::SAC1 => "sac1",
+            Preprocessing::SACOpt => "sac-opt",
+        };
+        let propagation = match self.propagation {
+            Propagation::ForwardChecking => "fc",
+            Propagation::MAC => "mac",
+        };
+        write!(f, "{}:{}", preprocessing, propagation)
     }
 }
 
-/// Implementation of the PC-2 algorithm by Mackworth 1977, specialized to work
-/// on graphs.
+impl FromStr for Consistency {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (preprocessing, propagation) = match s.split_once(':') {
+            Some((pre, prop)) => (pre, prop),
+            None => (s, "mac"),
+        };
+        let preprocessing = match preprocessing {
+            "none" => Preprocessing::None,
+            "ac1" => Preprocessing::AC1,
+            "ac3" => Preprocessing::AC3,
+            "ac2001" => Preprocessing::AC2001,
+            "sac1" => Preprocessing::SAC1,
+            "sac-opt" => Preprocessing::SACOpt,
+            _ => return Err("Unknown preprocessing consistency"),
+        };
+        let propagation = match propagation {
+            "fc" => Propagation::ForwardChecking,
+            "mac" => Propagation::MAC,
+            _ => return Err("Unknown propagation consistency"),
+        };
+        Ok(Consistency {
+            preprocessing,
+            propagation,
+        })
+    }
+}
+
+/// Implementation of the PC-2 algorithm by Mackworth 1977, specialized to find
+/// graph homomorphisms.
+///
+/// For every pair of vertices (x, y) of g0 a binary relation on the vertices
+/// of g1 is maintained, initialized with the pairs that are allowed by the
+/// edges between x and y and the lists of x and y. The relation of (x, x) is
+/// the diagonal on the list of x. A pair (a, b) is removed from the relation of
+/// (x, y) as long as some vertex z has no c such that (a, c) and (c, b) are in
+/// the relations of (x, z) and (z, y).
+///
+/// f represents a list of vertices for each vertex of g0. If there's no list
+/// specified for a vertex v, a list of all nodes of g1 is assigned to v.
+///
+/// Returns None, if an empty relation is derived for some pair of vertices,
+/// otherwise the (2,3)-minimal relations are returned.
+///
+/// # Examples
+///
+/// ```
+/// use tripolys::adjacency_list::AdjacencyList;
+/// use tripolys::consistency::{ac_3, pc_2_lists, Lists};
+///
+/// // A symmetric triangle does not map to a symmetric edge, which arc
+/// // consistency fails to detect
+/// let mut k3 = AdjacencyList::<u32>::new();
+/// let mut k2 = AdjacencyList::<u32>::new();
+/// for v in 0..3 {
+///     k3.add_vertex(v);
+///     k2.add_vertex(v % 2);
+/// }
+/// for (u, v) in [(0, 1), (1, 2), (2, 0)] {
+///     k3.add_edge(&u, &v);
+///     k3.add_edge(&v, &u);
+/// }
+/// k2.add_edge(&0, &1);
+/// k2.add_edge(&1, &0);
 ///
-/// Returns false, if an empty list is derived for some vertex v, true otherwise.
-pub fn pc_2<V0, V1>(g0: &AdjacencyList<V0>, g1: &AdjacencyList<V1>) -> bool
+/// assert!(ac_3(&k3, &k2).is_some());
+/// assert!(pc_2_lists(&k3, &k2, Lists::new()).is_none());
+///
+/// let relations = pc_2_lists(&k2, &k2, Lists::new()).unwrap();
+/// assert!(relations.is_23_minimal());
+/// assert_eq!(relations.get(&0, &1).unwrap().len(), 2);
+/// ```
+pub fn pc_2_lists<V0, V1, G0, G1>(
+    g0: &G0,
+    g1: &G1,
+    lists: Lists<V0, V1>,
+) -> Option<Relations<V0, V1>>
 where
+    G0: Graph<Vertex = V0>,
+    G1: Graph<Vertex = V1>,
     V0: VertexID + Debug,
-    V1: VertexID + Debug,
+    V1: DenseID + Debug,
 {
-    let mut lists = HashMap::<(V0, V0), Set<(V1, V1)>>::new();
-    let mut pending_list = HashSet::<(V0, V0, V0)>::new();
+    let mut network = PathNetwork::new(g0, g1, lists)?;
+    let n = network.vars.len();
+
+    // The relation of (y, x) is the converse of the one of (x, y), so only
+    // triples with x <= y are revised
+    let mut pending_list = VecDeque::<(usize, usize, usize)>::new();
+    let mut pending = vec![false; n * n * n];
+    let enqueue = |x: usize,
+                   y: usize,
+                   z: usize,
+                   pending: &mut Vec<bool>,
+                   pending_list: &mut VecDeque<(usize, usize, usize)>| {
+        let (x, y) = if x <= y { (x, y) } else { (y, x) };
+        if !pending[(x * n + y) * n + z] {
+            pending[(x * n + y) * n + z] = true;
+            pending_list.push_back((x, y, z));
+        }
+    };
 
-    let mut set = Set::<(V1, V1)>::new();
-    for u in g1.vertices() {
-        for v in g1.vertices() {
-            set.insert((u.clone(), v.clone()));
+    for x in 0..n {
+        for y in x..n {
+            for z in 0..n {
+                enqueue(x, y, z, &mut pending, &mut pending_list);
+            }
         }
     }
 
-    for u in g0.vertices() {
-        for v in g0.vertices() {
-            if u == v {
-                let mut s = Set::<(V1, V1)>::new();
-                for u in g1.vertices() {
-                    s.insert((u.clone(), u.clone()));
-                }
-                lists.insert((u.clone(), v.clone()), s);
-            } else if g0.has_edge(u, v) {
-                let s = g1.edges().collect::<Set<_>>();
-                lists.insert((u.clone(), v.clone()), s);
-            } else {
-                lists.insert((u.clone(), v.clone()), set.clone());
+    while let Some((x, y, z)) = pending_list.pop_front() {
+        pending[(x * n + y) * n + z] = false;
+        if network.path_reduce(x, y, z) {
+            // relation of x,y changed, was the empty relation derived?
+            if network.is_empty(x, y) {
+                return None;
+            }
+            for u in 0..n {
+                enqueue(x, u, y, &mut pending, &mut pending_list);
+                enqueue(y, u, x, &mut pending, &mut pending_list);
             }
-            for w in g0.vertices() {
-                pending_list.insert((u.clone(), w.clone(), v.clone()));
+        }
+    }
+    Some(network.into_relations())
+}
+
+/// A modification of `pc_2_lists` that is initialized with a list of all nodes
+/// of g1 for each node in g0.
+pub fn pc_2<V0, V1, G0, G1>(g0: &G0, g1: &G1) -> Option<Relations<V0, V1>>
+where
+    G0: Graph<Vertex = V0>,
+    G1: Graph<Vertex = V1>,
+    V0: VertexID + Debug,
+    V1: DenseID + Debug,
+{
+    pc_2_lists(g0, g1, Lists::new())
+}
+
+/// A variant of `pc_2_lists` in the style of PC-4 by Han and Lee 1988.
+///
+/// Instead of revising whole relations, the number of supports of each pair
+/// (a, b) in the relation of (x, y) is counted for every vertex z, that is the
+/// number of c such that (a, c) and (c, b) are in the relations of (x, z) and
+/// (z, y). A pair is removed as soon as one of its counters drops to zero, and
+/// its removal only decrements the counters it contributed to. This needs
+/// memory for |V(g0)|³·|V(g1)|² counters, but avoids the repeated revisions of
+/// PC-2.
+///
+/// Returns None, if an empty relation is derived for some pair of vertices,
+/// otherwise the same (2,3)-minimal relations as `pc_2_lists` are returned.
+pub fn pc_4_lists<V0, V1, G0, G1>(
+    g0: &G0,
+    g1: &G1,
+    lists: Lists<V0, V1>,
+) -> Option<Relations<V0, V1>>
+where
+    G0: Graph<Vertex = V0>,
+    G1: Graph<Vertex = V1>,
+    V0: VertexID + Debug,
+    V1: DenseID + Debug,
+{
+    let mut network = PathNetwork::new(g0, g1, lists)?;
+    let (n, d) = (network.vars.len(), network.vals.len());
+    let counter =
+        |x: usize, y: usize, z: usize, a: usize, b: usize| (((x * n + y) * n + z) * d + a) * d + b;
+
+    let mut counters = vec![0u32; n * n * n * d * d];
+    let mut pending_list = VecDeque::<(usize, usize, usize, usize)>::new();
+    let mut pending = vec![false; n * n * d * d];
+
+    for x in 0..n {
+        for y in 0..n {
+            for z in 0..n {
+                for a in 0..d {
+                    for b in 0..d {
+                        if !network.has(x, y, a, b) {
+                            continue;
+                        }
+                        let supports = (0..d)
+                            .filter(|&c| network.has(x, z, a, c) && network.has(z, y, c, b))
+                            .count();
+                        counters[counter(x, y, z, a, b)] = supports as u32;
+                        if supports == 0 && !pending[network.index(x, y, a, b)] {
+                            pending[network.index(x, y, a, b)] = true;
+                            pending_list.push_back((x, y, a, b));
+                        }
+                    }
+                }
             }
         }
     }
-    while !pending_list.is_empty() {
-        let (x, y, z) = pending_list.iter().cloned().next().unwrap();
-        pending_list.remove(&(x.clone(), y.clone(), z.clone()));
-        if path_reduce(&x, &y, &z, &mut lists) {
-            // list of x,y changed, was the empty list derived?
-            if lists.get(&(x.clone(), y.clone())).unwrap().is_empty() {
-                return false;
+
+    while let Some((x, y, a, b)) = pending_list.pop_front() {
+        // Both the pair and its converse are removed one at a time, such that
+        // each support is withdrawn exactly once
+        for &(x, y, a, b) in &[(x, y, a, b), (y, x, b, a)] {
+            if !network.has(x, y, a, b) {
+                continue;
             }
-            for u in g0.vertices() {
-                if *u != x && *u != y {
-                    pending_list.insert((u.clone(), x.clone(), y.clone()));
-                    pending_list.insert((u.clone(), y.clone(), x.clone()));
+            network.remove(x, y, a, b);
+            if network.is_empty(x, y) {
+                return None;
+            }
+            let mut withdraw = |p: usize, q: usize, z: usize, e: usize, f: usize| {
+                if network.has(p, q, e, f) {
+                    let i = counter(p, q, z, e, f);
+                    counters[i] -= 1;
+                    if counters[i] == 0 && !pending[network.index(p, q, e, f)] {
+                        pending[network.index(p, q, e, f)] = true;
+                        pending_list.push_back((p, q, e, f));
+                    }
+                }
+            };
+            for u in 0..n {
+                for e in 0..d {
+                    // (a, b) supported (a, e) in the relation of (x, u) via y
+                    if network.has(y, u, b, e) {
+                        withdraw(x, u, y, a, e);
+                    }
+                    // (a, b) supported (e, b) in the relation of (u, y) via x
+                    if network.has(u, x, e, a) {
+                        withdraw(u, y, x, e, b);
+                    }
                 }
             }
         }
     }
-    true
+    Some(network.into_relations())
 }
 
-// Implementation of the path-reduce operation from pc2.
-// Returns true, if the list of x,y was reduced, false otherwise.
-fn path_reduce<V0, V1>(x: &V0, y: &V0, z: &V0, lists: &mut HashMap<(V0, V0), Set<(V1, V1)>>) -> bool
+/// A modification of `pc_4_lists` that is initialized with a list of all nodes
+/// of g1 for each node in g0.
+pub fn pc_4<V0, V1, G0, G1>(g0: &G0, g1: &G1) -> Option<Relations<V0, V1>>
 where
-    V0: Eq + Clone + Hash + Debug,
-    V1: Eq + Clone + Hash + Debug,
+    G0: Graph<Vertex = V0>,
+    G1: Graph<Vertex = V1>,
+    V0: VertexID + Debug,
+    V1: DenseID + Debug,
 {
-    for (a, b) in lists.get(&(x.clone(), y.clone())).unwrap().clone().iter() {
-        'middle: for (u, v) in lists.get(&(x.clone(), z.clone())).unwrap().iter() {
-            if a == u {
-                for (c, d) in lists.get(&(y.clone(), z.clone())).unwrap().iter() {
-                    if c == b && d == v {
-                        break 'middle;
+    pc_4_lists(g0, g1, Lists::new())
+}
+
+// The binary relations of the path-consistency algorithms, with the vertices
+// of g0 and g1 replaced by their position in `vars` and `vals`.
+struct PathNetwork<V0, V1> {
+    vars: Vec<V0>,
+    vals: Vec<V1>,
+    relations: Vec<bool>,
+    sizes: Vec<usize>,
+}
+
+impl<V0: VertexID, V1: DenseID> PathNetwork<V0, V1> {
+    // Returns None, if the list of some vertex is empty.
+    fn new<G0, G1>(g0: &G0, g1: &G1, lists: Lists<V0, V1>) -> Option<PathNetwork<V0, V1>>
+    where
+        G0: Graph<Vertex = V0>,
+        G1: Graph<Vertex = V1>,
+    {
+        let vars = g0.vertices().cloned().collect::<Vec<_>>();
+        let vals = g1.vertices().cloned().collect::<Vec<_>>();
+        let (n, d) = (vars.len(), vals.len());
+
+        let domains = vars
+            .iter()
+            .map(|x| match lists.get(x) {
+                Some(list) => (0..d).filter(|&a| list.contains(&vals[a])).collect(),
+                None => (0..d).collect::<Vec<_>>(),
+            })
+            .collect::<Vec<_>>();
+
+        let mut network = PathNetwork {
+            relations: vec![false; n * n * d * d],
+            sizes: vec![0; n * n],
+            vars,
+            vals,
+        };
+        for x in 0..n {
+            if domains[x].is_empty() {
+                return None;
+            }
+            for y in 0..n {
+                let (u, v) = (&network.vars[x], &network.vars[y]);
+                let (forward, backward) = (g0.has_edge(u, v), g0.has_edge(v, u));
+                for &a in &domains[x] {
+                    for &b in &domains[y] {
+                        let (s, t) = (&network.vals[a], &network.vals[b]);
+                        if (x != y || a == b)
+                            && (!forward || g1.has_edge(s, t))
+                            && (!backward || g1.has_edge(t, s))
+                        {
+                            let i = network.index(x, y, a, b);
+                            network.relations[i] = true;
+                            network.sizes[x * n + y] += 1;
+                        }
+                    }
+                }
+            }
+        }
+        Some(network)
+    }
+
+    fn index(&self, x: usize, y: usize, a: usize, b: usize) -> usize {
+        let (n, d) = (self.vars.len(), self.vals.len());
+        ((x * n + y) * d + a) * d + b
+    }
+
+    fn has(&self, x: usize, y: usize, a: usize, b: usize) -> bool {
+        self.relations[self.index(x, y, a, b)]
+    }
+
+    fn is_empty(&self, x: usize, y: usize) -> bool {
+        self.sizes[x * self.vars.len() + y] == 0
+    }
+
+    // Removes (a, b) from the relation of (x, y) only, its converse is left to
+    // the caller.
+    fn remove(&mut self, x: usize, y: usize, a: usize, b: usize) {
+        let i = self.index(x, y, a, b);
+        self.relations[i] = false;
+        self.sizes[x * self.vars.len() + y] -= 1;
+    }
+
+    // Implementation of the path-reduce operation from pc2. Removes all pairs
+    // (a, b) from the relation of (x, y) (and (b, a) from the one of (y, x))
+    // that have no c with (a, c) and (c, b) in the relations of (x, z) and
+    // (z, y). Returns true, if the relation of x,y was reduced, false
+    // otherwise.
+    fn path_reduce(&mut self, x: usize, y: usize, z: usize) -> bool {
+        let d = self.vals.len();
+        let mut changed = false;
+        for a in 0..d {
+            for b in 0..d {
+                if self.has(x, y, a, b)
+                    && !(0..d).any(|c| self.has(x, z, a, c) && self.has(z, y, c, b))
+                {
+                    self.remove(x, y, a, b);
+                    if x != y || a != b {
+                        self.remove(y, x, b, a);
                     }
+                    changed = true;
                 }
             }
         }
+        changed
+    }
+
+    fn into_relations(self) -> Relations<V0, V1> {
+        let (n, d) = (self.vars.len(), self.vals.len());
+        let mut relations = HashMap::new();
+        for x in 0..n {
+            for y in 0..n {
+                let relation = (0..d)
+                    .flat_map(|a| (0..d).map(move |b| (a, b)))
+                    .filter(|&(a, b)| self.has(x, y, a, b))
+                    .map(|(a, b)| (self.vals[a].clone(), self.vals[b].clone()))
+                    .collect::<HashSet<_>>();
+                relations.insert((self.vars[x].clone(), self.vars[y].clone()), relation);
+            }
+        }
+        Relations { relations }
     }
-    false
 }
 
 /// Implementation of the SAC-Opt algorithm due to Bessiere and Debruyne 2008,
@@ -471,19 +964,18 @@ where
 ///
 /// Returns None, if an empty list is derived for some vertex v, otherwise
 /// singleton-arc-consistent lists are returned.
-pub fn sac_opt_lists<V0, V1>(
-    g0: &AdjacencyList<V0>,
-    g1: &AdjacencyList<V1>,
+pub fn sac_opt_lists<V0, V1, G0, G1>(
+    g0: &G0,
+    g1: &G1,
     lists: Lists<V0, V1>,
 ) -> Option<Lists<V0, V1>>
 where
+    G0: Graph<Vertex = V0>,
+    G1: Graph<Vertex = V1>,
     V0: VertexID + Debug,
-    V1: VertexID + Debug,
+    V1: DenseID + Debug,
 {
-    let mut lists = match ac_3_lists(g0, g1, lists) {
-        Some(v) => v,
-        None => return None,
-    };
+    let mut lists = ac_2001_lists(g0, g1, lists)?;
 
     let mut pending_list = HashSet::<(V0, V1)>::new();
     let mut ds = HashMap::<(V0, V1), Lists<V0, V1>>::new();
@@ -514,7 +1006,7 @@ where
         for (x, y) in q.get(&(i.clone(), a.clone())).unwrap().iter() {
             d.get_mut(x).unwrap().remove(y);
         }
-        if let Some(v) = ac_3_lists(g0, g1, d.clone()) {
+        if let Some(v) = ac_2001_lists(g0, g1, d.clone()) {
             q.get_mut(&(i.clone(), a.clone())).unwrap().clear();
             *d = v;
         } else {
@@ -538,25 +1030,30 @@ where
 
 /// A modification of `sac_opt_lists` that is initialized with a list of all nodes
 /// of g1 for each node in g0.
-pub fn sac_opt<V0, V1>(g0: &AdjacencyList<V0>, g1: &AdjacencyList<V1>) -> Option<Lists<V0, V1>>
+pub fn sac_opt<V0, V1, G0, G1>(g0: &G0, g1: &G1) -> Option<Lists<V0, V1>>
 where
+    G0: Graph<Vertex = V0>,
+    G1: Graph<Vertex = V1>,
     V0: VertexID + Debug,
-    V1: VertexID + Debug,
+    V1: DenseID + Debug,
 {
     sac_opt_lists(g0, g1, Lists::new())
 }
 
-/// A list implemented as a wrapper around `HashSet`
-#[derive(Clone, Debug, Default)]
-pub struct List<T: Eq + Hash> {
-    list: HashSet<T>,
+/// A list implemented as a bitset, where the bit of a vertex is given by its
+/// dense index, see `DenseID`.
+#[derive(Clone)]
+pub struct List<T: DenseID> {
+    bits: Vec<u64>,
+    marker: PhantomData<T>,
 }
 
-impl<T: Eq + Hash + Clone> List<T> {
+impl<T: DenseID> List<T> {
     /// Creates an empty `List`.
     pub fn new() -> List<T> {
         List {
-            list: HashSet::<T>::new(),
+            bits: Vec::new(),
+            marker: PhantomData,
         }
     }
 
@@ -566,61 +1063,126 @@ impl<T: Eq + Hash + Clone> List<T> {
     ///
     /// If the list did have this value present, `false` is returned.
     pub fn insert(&mut self, v: T) -> bool {
-        self.list.insert(v)
+        let (word, bit) = (v.index() / 64, v.index() % 64);
+        if word >= self.bits.len() {
+            self.bits.resize(word + 1, 0);
+        }
+        let inserted = self.bits[word] & (1 << bit) == 0;
+        self.bits[word] |= 1 << bit;
+        inserted
     }
 
-    /// Returns the number of elements in the list.
+    /// Returns the number of elements in the list, i.e. the popcount of the
+    /// bitset.
     pub fn size(&self) -> usize {
-        self.list.len()
+        self.bits.iter().map(|w| w.count_ones() as usize).sum()
     }
 
-    /// An iterator visiting all elements in arbitrary order.
-    /// The iterator element type is `(&'a T)`.
-    pub fn iter(&self) -> impl Iterator<Item = &T> {
-        self.list.iter()
+    /// An iterator visiting all elements in ascending order of their index.
+    /// The iterator element type is `T`.
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        self.bits.iter().enumerate().flat_map(|(i, &word)| {
+            let mut word = word;
+            std::iter::from_fn(move || {
+                if word == 0 {
+                    return None;
+                }
+                let bit = word.trailing_zeros() as usize;
+                word &= word - 1;
+                Some(T::from_index(i * 64 + bit))
+            })
+        })
     }
 
     /// Returns `true` if the list contains no elements.
     pub fn is_empty(&self) -> bool {
-        self.list.is_empty()
+        self.bits.iter().all(|&w| w == 0)
     }
 
     /// Removes a value from the list, returning `true` if the key was previously
     /// in the list, `false` otherwise.
     pub fn remove(&mut self, v: &T) -> bool {
-        self.list.remove(v)
+        let (word, bit) = (v.index() / 64, v.index() % 64);
+        match self.bits.get_mut(word) {
+            Some(w) if *w & (1 << bit) != 0 => {
+                *w &= !(1 << bit);
+                true
+            }
+            _ => false,
+        }
     }
 
+    /// Returns `true` if the list contains the value.
+    pub fn contains(&self, v: &T) -> bool {
+        let (word, bit) = (v.index() / 64, v.index() % 64);
+        self.bits.get(word).is_some_and(|w| w & (1 << bit) != 0)
+    }
+
+    /// Adds all values of `v` to the list.
     pub fn merge(&mut self, v: &List<T>) {
-        for elem in v.iter() {
-            self.insert(elem.clone());
+        if v.bits.len() > self.bits.len() {
+            self.bits.resize(v.bits.len(), 0);
+        }
+        for (w, u) in self.bits.iter_mut().zip(&v.bits) {
+            *w |= u;
         }
     }
 
-    pub fn pop(&mut self) -> Option<T> {
-        if let Some(elem) = self.list.iter().cloned().next() {
-            self.list.remove(&elem);
-            return Some(elem);
+    /// Returns the values that are in `self` and in `other`.
+    pub fn intersection(&self, other: &List<T>) -> List<T> {
+        List {
+            bits: self.bits.iter().zip(&other.bits).map(|(w, u)| w & u).collect(),
+            marker: PhantomData,
         }
-        None
+    }
+
+    /// Returns the values that are in `self` but not in `other`.
+    pub fn difference(&self, other: &List<T>) -> List<T> {
+        let bits = self.bits.iter().enumerate();
+        List {
+            bits: bits.map(|(i, w)| w & !other.bits.get(i).unwrap_or(&0)).collect(),
+            marker: PhantomData,
+        }
+    }
+
+    /// Removes the value with the smallest index from the list and returns it.
+    pub fn pop(&mut self) -> Option<T> {
+        let elem = self.iter().next()?;
+        self.remove(&elem);
+        Some(elem)
+    }
+}
+
+impl<T: DenseID> Default for List<T> {
+    fn default() -> Self {
+        List::new()
+    }
+}
+
+impl<T: DenseID + Debug> Debug for List<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
     }
 }
 
-impl<T: Eq + Hash> FromIterator<T> for List<T> {
+impl<T: DenseID> FromIterator<T> for List<T> {
     fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
-        List {
-            list: iter.into_iter().collect::<HashSet<_>>(),
+        let mut list = List::new();
+        for v in iter {
+            list.insert(v);
         }
+        list
     }
 }
 
-/// A list implemented as a wrapper around `HashSet`
+/// A map from the vertices of g0 to their lists, implemented as a wrapper
+/// around `HashMap`
 #[derive(Clone, Debug, Default)]
-pub struct Lists<V0: Eq + Hash, V1: Eq + Hash + Clone> {
+pub struct Lists<V0: Eq + Hash, V1: DenseID> {
     lists: HashMap<V0, List<V1>>,
 }
 
-impl<V0: Eq + Hash + Clone, V1: Eq + Hash + Clone> Lists<V0, V1> {
+impl<V0: Eq + Hash + Clone, V1: DenseID> Lists<V0, V1> {
     /// Creates a new, empty set of lists.
     pub fn new() -> Lists<V0, V1> {
         Lists {
@@ -642,7 +1204,7 @@ impl<V0: Eq + Hash + Clone, V1: Eq + Hash + Clone> Lists<V0, V1> {
     /// An iterator visiting all variable-list pairs in arbitrary order.
     /// The iterator element type is `(&'a V0, &'a Set<V1>)`.
     ///
-    pub fn iter<'a>(&'a self) -> impl Iterator<Item = (&V0, &List<V1>)> + 'a {
+    pub fn iter(&self) -> impl Iterator<Item = (&V0, &List<V1>)> {
         self.lists.iter()
     }
 
@@ -681,6 +1243,20 @@ impl<V0: Eq + Hash + Clone, V1: Eq + Hash + Clone> Lists<V0, V1> {
         self.lists.len()
     }
 
+    /// Returns the lists of the vertices that are in `self` but not in
+    /// `other`, for each variable of `self`.
+    pub fn difference(&self, other: &Lists<V0, V1>) -> Lists<V0, V1> {
+        self.iter()
+            .map(|(k, v)| {
+                let list = match other.get(k) {
+                    Some(w) => v.difference(w),
+                    None => v.clone(),
+                };
+                (k.clone(), list)
+            })
+            .collect()
+    }
+
     pub fn merge(&mut self, other: &Lists<V0, V1>) {
         for (k, v) in other.iter() {
             if self.contains_variable(k) {
@@ -692,7 +1268,15 @@ impl<V0: Eq + Hash + Clone, V1: Eq + Hash + Clone> Lists<V0, V1> {
     }
 }
 
-impl<V0: Eq + Hash, V1: Eq + Hash + Clone> IntoIterator for Lists<V0, V1> {
+impl<V0: Eq + Hash, V1: DenseID> FromIterator<(V0, List<V1>)> for Lists<V0, V1> {
+    fn from_iter<I: IntoIterator<Item = (V0, List<V1>)>>(iter: I) -> Self {
+        Lists {
+            lists: iter.into_iter().collect::<HashMap<_, _>>(),
+        }
+    }
+}
+
+impl<V0: Eq + Hash, V1: DenseID> IntoIterator for Lists<V0, V1> {
     type Item = (V0, List<V1>);
     type IntoIter = std::collections::hash_map::IntoIter<V0, List<V1>>;
 
@@ -700,3 +1284,64 @@ impl<V0: Eq + Hash, V1: Eq + Hash + Clone> IntoIterator for Lists<V0, V1> {
         self.lists.into_iter()
     }
 }
+
+/// The binary relations derived by a path-consistency algorithm, one for each
+/// ordered pair of vertices of g0, implemented as a wrapper around `HashMap`.
+///
+/// The relation of (x, y) contains the pairs (a, b) of vertices of g1 such that
+/// x and y can still be mapped to a and b, respectively. The relation of (y, x)
+/// is its converse and the relation of (x, x) is the diagonal on the list of x.
+#[derive(Clone, Debug, Default)]
+pub struct Relations<V0: Eq + Hash, V1: DenseID> {
+    relations: HashMap<(V0, V0), HashSet<(V1, V1)>>,
+}
+
+impl<V0: Eq + Hash + Clone, V1: DenseID> Relations<V0, V1> {
+    /// Returns the relation of the pair (x, y).
+    pub fn get(&self, x: &V0, y: &V0) -> Option<&HashSet<(V1, V1)>> {
+        self.relations.get(&(x.clone(), y.clone()))
+    }
+
+    /// An iterator visiting all pair-relation pairs in arbitrary order.
+    /// The iterator element type is `(&'a (V0, V0), &'a HashSet<(V1, V1)>)`.
+    ///
+    pub fn iter(&self) -> impl Iterator<Item = (&(V0, V0), &HashSet<(V1, V1)>)> {
+        self.relations.iter()
+    }
+
+    /// Returns the lists that remain for each vertex, i.e. the projections of
+    /// the relations of the pairs (x, x).
+    pub fn lists(&self) -> Lists<V0, V1> {
+        self.relations
+            .iter()
+            .filter(|((x, y), _)| x == y)
+            .map(|((x, _), r)| (x.clone(), r.iter().map(|(a, _)| a.clone()).collect()))
+            .collect()
+    }
+
+    /// Returns `true` if the relations are (2,3)-minimal, that is if for all
+    /// vertices x, y, z of g0 each pair (a, b) in the relation of (x, y) extends
+    /// to some c with (a, c) and (c, b) in the relations of (x, z) and (z, y).
+    pub fn is_23_minimal(&self) -> bool {
+        let vertices = self.lists().variables().cloned().collect::<Vec<_>>();
+        for x in &vertices {
+            for y in &vertices {
+                for z in &vertices {
+                    let (xy, xz, zy) = match (self.get(x, y), self.get(x, z), self.get(z, y)) {
+                        (Some(xy), Some(xz), Some(zy)) => (xy, xz, zy),
+                        _ => return false,
+                    };
+                    for (a, b) in xy.iter() {
+                        if !xz
+                            .iter()
+                            .any(|(u, c)| u == a && zy.contains(&(c.clone(), b.clone())))
+                        {
+                            return false;
+                        }
+                    }
+                }
+            }
+        }
+        true
+    }
+}
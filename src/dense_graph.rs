@@ -0,0 +1,451 @@
+//! A graph whose vertices are interned to dense indices.
+use std::collections::HashMap;
+
+use rayon::prelude::*;
+
+use crate::{
+    adjacency_list::{AdjacencyList, VertexID},
+    graph::{Graph, MutableGraph},
+};
+
+/// A graph that interns its vertices to dense `u32` indices, implemented as a
+/// wrapper struct around two vectors of adjacency rows. The row at index i
+/// contains the sorted indices of all successors resp. predecessors of the
+/// vertex with index i, so that `has_edge` is a binary search instead of a
+/// linear scan.
+///
+/// Vertices keep the order in which they were added. Removing a vertex moves
+/// the last vertex into the freed index, so the indices stay dense.
+#[derive(Debug, Clone)]
+pub struct DenseGraph<V: VertexID> {
+    // Index -> Vertex
+    labels: Vec<V>,
+    // Vertex -> Index
+    indices: HashMap<V, u32>,
+    // Index -> Out-Edges
+    out_edges: Vec<Vec<u32>>,
+    // Index -> In-Edges
+    in_edges: Vec<Vec<u32>>,
+}
+
+impl<V: VertexID> Default for DenseGraph<V> {
+    fn default() -> Self {
+        DenseGraph::new()
+    }
+}
+
+impl<V: VertexID> DenseGraph<V> {
+    /// Creates an empty `DenseGraph`.
+    pub fn new() -> DenseGraph<V> {
+        DenseGraph {
+            labels: Vec::new(),
+            indices: HashMap::new(),
+            out_edges: Vec::new(),
+            in_edges: Vec::new(),
+        }
+    }
+
+    /// Returns the index of the vertex `v`, if it is contained in the graph.
+    pub fn index(&self, v: &V) -> Option<u32> {
+        self.indices.get(v).copied()
+    }
+
+    /// Returns the vertex with index `i`.
+    ///
+    /// # Panics
+    ///
+    /// Panics, if there's no vertex with index `i`.
+    pub fn label(&self, i: u32) -> &V {
+        &self.labels[i as usize]
+    }
+
+    /// Returns the sorted indices of the successors of the vertex with index `i`.
+    pub fn out_row(&self, i: u32) -> &[u32] {
+        &self.out_edges[i as usize]
+    }
+
+    /// Returns the sorted indices of the predecessors of the vertex with index `i`.
+    pub fn in_row(&self, i: u32) -> &[u32] {
+        &self.in_edges[i as usize]
+    }
+
+    /// Adds a vertex to the graph.
+    ///
+    /// If the graph did not have this vertex present, `true` is returned.
+    ///
+    /// If the graph did have this vertex present, `false` is returned.
+    pub fn add_vertex(&mut self, v: V) -> bool {
+        if self.indices.contains_key(&v) {
+            return false;
+        }
+        self.indices.insert(v.clone(), self.labels.len() as u32);
+        self.labels.push(v);
+        self.out_edges.push(Vec::new());
+        self.in_edges.push(Vec::new());
+        true
+    }
+
+    /// Removes a vertex from the graph, returning `true` if the vertex was
+    /// previously in the graph, `false` otherwise.
+    pub fn remove_vertex(&mut self, v: &V) -> bool {
+        if let Some(i) = self.index(v) {
+            self.remove_index(i);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns `true` if the graph contains the given vertex, false otherwise.
+    pub fn has_vertex(&self, v: &V) -> bool {
+        self.indices.contains_key(v)
+    }
+
+    /// Adds an edge to the graph.
+    ///
+    /// If the graph did not have this edge present, `true` is returned.
+    ///
+    /// If the graph did have this edge present, `false` is returned.
+    ///
+    /// # Panics
+    ///
+    /// Panics, if one of the vertices doesn't exist.
+    pub fn add_edge(&mut self, u: &V, v: &V) -> bool {
+        let i = self.index(u).expect("vertex does not exist!");
+        let j = self.index(v).expect("vertex does not exist!");
+        self.add_edge_index(i, j)
+    }
+
+    /// Returns `true` if the graph contains the given edge, false otherwise.
+    pub fn has_edge(&self, u: &V, v: &V) -> bool {
+        match (self.index(u), self.index(v)) {
+            (Some(i), Some(j)) => self.has_edge_index(i, j),
+            _ => false,
+        }
+    }
+
+    /// Returns `true` if there's an edge from the vertex with index `i` to the
+    /// vertex with index `j`, false otherwise.
+    pub fn has_edge_index(&self, i: u32, j: u32) -> bool {
+        let out_row = &self.out_edges[i as usize];
+        let in_row = &self.in_edges[j as usize];
+        if out_row.len() <= in_row.len() {
+            out_row.binary_search(&j).is_ok()
+        } else {
+            in_row.binary_search(&i).is_ok()
+        }
+    }
+
+    /// Contracts the vertex `v` with the vertex `u` so that the resulting vertex
+    /// has id `u`.
+    pub fn contract_vertices(&mut self, u: &V, v: &V) {
+        assert!(u != v, "vertex can not be contracted with itself!");
+        let i = self.index(u).expect("vertex does not exist!");
+        let j = self.index(v).expect("vertex does not exist!");
+
+        for w in self.in_edges[j as usize].clone() {
+            self.add_edge_index(if w == j { i } else { w }, i);
+        }
+        for w in self.out_edges[j as usize].clone() {
+            self.add_edge_index(i, if w == j { i } else { w });
+        }
+        self.remove_index(j);
+    }
+
+    /// Returns an iterator over references to all of the vertices in the graph.
+    pub fn vertices(&self) -> impl Iterator<Item = &V> {
+        self.labels.iter()
+    }
+
+    /// Returns an iterator over all of the edges in the graph.
+    pub fn edges(&self) -> impl Iterator<Item = (V, V)> + '_ {
+        self.out_edges.iter().enumerate().flat_map(move |(i, row)| {
+            row.iter()
+                .map(move |&j| (self.labels[i].clone(), self.labels[j as usize].clone()))
+        })
+    }
+
+    /// Performs the union of G and H, which is the graph with vertex set V(G) ∪
+    /// V(H).
+    pub fn union(&self, other: &DenseGraph<V>) -> DenseGraph<V> {
+        let mut graph = self.clone();
+        for v in other.vertices() {
+            graph.add_vertex(v.clone());
+        }
+        for (u, v) in other.edges() {
+            graph.add_edge(&u, &v);
+        }
+        graph
+    }
+
+    /// Returns a vector of the (weakly connected) components of the graph.
+    /// The vertices of each component keep their relative order.
+    pub fn components(&self) -> Vec<DenseGraph<V>> {
+        let mut visited = vec![false; self.labels.len()];
+        let mut components = Vec::new();
+
+        for start in 0..self.labels.len() {
+            if visited[start] {
+                continue;
+            }
+            visited[start] = true;
+            let mut component = vec![start as u32];
+            let mut stack = vec![start as u32];
+
+            while let Some(i) = stack.pop() {
+                let out_row = self.out_edges[i as usize].iter();
+                for &j in out_row.chain(self.in_edges[i as usize].iter()) {
+                    if !visited[j as usize] {
+                        visited[j as usize] = true;
+                        component.push(j);
+                        stack.push(j);
+                    }
+                }
+            }
+            component.sort_unstable();
+            components.push(self.induced(&component));
+        }
+        components
+    }
+
+    // Returns the subgraph induced by the sorted indices `component`.
+    fn induced(&self, component: &[u32]) -> DenseGraph<V> {
+        let map = component
+            .iter()
+            .enumerate()
+            .map(|(new, &old)| (old, new as u32))
+            .collect::<HashMap<_, _>>();
+        let translate = |row: &[u32]| {
+            row.iter()
+                .filter_map(|j| map.get(j).copied())
+                .collect::<Vec<_>>()
+        };
+        let labels = component
+            .iter()
+            .map(|&i| self.labels[i as usize].clone())
+            .collect::<Vec<_>>();
+
+        DenseGraph {
+            indices: labels
+                .iter()
+                .cloned()
+                .enumerate()
+                .map(|(i, v)| (v, i as u32))
+                .collect(),
+            labels,
+            out_edges: component
+                .iter()
+                .map(|&i| translate(&self.out_edges[i as usize]))
+                .collect(),
+            in_edges: component
+                .iter()
+                .map(|&i| translate(&self.in_edges[i as usize]))
+                .collect(),
+        }
+    }
+
+    fn add_edge_index(&mut self, i: u32, j: u32) -> bool {
+        let out_row = &mut self.out_edges[i as usize];
+        match out_row.binary_search(&j) {
+            Ok(_) => false,
+            Err(pos) => {
+                out_row.insert(pos, j);
+                let in_row = &mut self.in_edges[j as usize];
+                let pos = in_row.binary_search(&i).unwrap_err();
+                in_row.insert(pos, i);
+                true
+            }
+        }
+    }
+
+    // Removes the vertex with index i and moves the last vertex into its place.
+    fn remove_index(&mut self, i: u32) {
+        for w in std::mem::take(&mut self.out_edges[i as usize]) {
+            if w != i {
+                remove_sorted(&mut self.in_edges[w as usize], i);
+            }
+        }
+        for w in std::mem::take(&mut self.in_edges[i as usize]) {
+            if w != i {
+                remove_sorted(&mut self.out_edges[w as usize], i);
+            }
+        }
+
+        let last = self.labels.len() as u32 - 1;
+        let label = self.labels.swap_remove(i as usize);
+        self.indices.remove(&label);
+        self.out_edges.swap_remove(i as usize);
+        self.in_edges.swap_remove(i as usize);
+
+        if i == last {
+            return;
+        }
+        self.indices.insert(self.labels[i as usize].clone(), i);
+
+        for w in self.out_edges[i as usize].clone() {
+            let w = if w == last { i } else { w };
+            relabel_sorted(&mut self.in_edges[w as usize], last, i);
+        }
+        for w in self.in_edges[i as usize].clone() {
+            let w = if w == last { i } else { w };
+            relabel_sorted(&mut self.out_edges[w as usize], last, i);
+        }
+    }
+}
+
+fn remove_sorted(row: &mut Vec<u32>, x: u32) {
+    if let Ok(pos) = row.binary_search(&x) {
+        row.remove(pos);
+    }
+}
+
+fn relabel_sorted(row: &mut Vec<u32>, from: u32, to: u32) {
+    remove_sorted(row, from);
+    if let Err(pos) = row.binary_search(&to) {
+        row.insert(pos, to);
+    }
+}
+
+impl<V: VertexID + Sync + Send> DenseGraph<V> {
+    /// Returns the k-ary product graph. The resulting graph uses `Vec` to
+    /// represent the resulting tuples. A tuple (v_1, ..., v_k) gets the index
+    /// that is represented by the indices of v_1, ..., v_k in mixed radix, so
+    /// the adjacency rows are built in sorted order without any lookups. The
+    /// method uses parallelism.
+    pub fn power(&self, k: u32) -> DenseGraph<Vec<V>> {
+        let n = self.labels.len();
+        let count = n.pow(k);
+        let digits = |mut x: usize| {
+            let mut digits = vec![0; k as usize];
+            for d in digits.iter_mut().rev() {
+                *d = x % n;
+                x /= n;
+            }
+            digits
+        };
+        let product = |rows: &[Vec<u32>], x: usize| {
+            let mut res = vec![0u32];
+            for d in digits(x) {
+                res = res
+                    .iter()
+                    .flat_map(|r| rows[d].iter().map(move |j| r * n as u32 + j))
+                    .collect();
+            }
+            res
+        };
+
+        let labels = (0..count)
+            .into_par_iter()
+            .map(|x| {
+                digits(x)
+                    .into_iter()
+                    .map(|d| self.labels[d].clone())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let indices = labels
+            .par_iter()
+            .enumerate()
+            .map(|(i, v)| (v.clone(), i as u32))
+            .collect::<HashMap<_, _>>();
+        let out_edges = (0..count)
+            .into_par_iter()
+            .map(|x| product(&self.out_edges, x))
+            .collect::<Vec<_>>();
+        let in_edges = (0..count)
+            .into_par_iter()
+            .map(|x| product(&self.in_edges, x))
+            .collect::<Vec<_>>();
+
+        DenseGraph {
+            labels,
+            indices,
+            out_edges,
+            in_edges,
+        }
+    }
+}
+
+impl<V: VertexID> From<&AdjacencyList<V>> for DenseGraph<V> {
+    fn from(list: &AdjacencyList<V>) -> Self {
+        let mut graph = DenseGraph::new();
+        for v in list.vertices() {
+            graph.add_vertex(v.clone());
+        }
+        for (u, v) in list.edges() {
+            graph.add_edge(&u, &v);
+        }
+        graph
+    }
+}
+
+impl<V: VertexID> Graph for DenseGraph<V> {
+    type Vertex = V;
+
+    fn vertices(&self) -> impl Iterator<Item = &V> {
+        DenseGraph::vertices(self)
+    }
+
+    fn vertex_count(&self) -> usize {
+        self.labels.len()
+    }
+
+    fn has_vertex(&self, v: &V) -> bool {
+        DenseGraph::has_vertex(self, v)
+    }
+
+    fn edges(&self) -> impl Iterator<Item = (V, V)> {
+        DenseGraph::edges(self)
+    }
+
+    fn has_edge(&self, u: &V, v: &V) -> bool {
+        DenseGraph::has_edge(self, u, v)
+    }
+
+    fn out_neighbors(&self, v: &V) -> impl Iterator<Item = V> {
+        let i = self.index(v).expect("vertex does not exist!");
+        self.out_edges[i as usize]
+            .iter()
+            .map(move |&j| self.labels[j as usize].clone())
+    }
+
+    fn in_neighbors(&self, v: &V) -> impl Iterator<Item = V> {
+        let i = self.index(v).expect("vertex does not exist!");
+        self.in_edges[i as usize]
+            .iter()
+            .map(move |&j| self.labels[j as usize].clone())
+    }
+
+    fn degree(&self, v: &V) -> usize {
+        let i = self.index(v).expect("vertex does not exist!") as usize;
+        self.out_edges[i].len() + self.in_edges[i].len()
+    }
+}
+
+impl<V: VertexID + Sync + Send> MutableGraph for DenseGraph<V> {
+    type Power = DenseGraph<Vec<V>>;
+
+    fn add_vertex(&mut self, v: V) -> bool {
+        DenseGraph::add_vertex(self, v)
+    }
+
+    fn add_edge(&mut self, u: &V, v: &V) -> bool {
+        DenseGraph::add_edge(self, u, v)
+    }
+
+    fn contract_vertices(&mut self, u: &V, v: &V) {
+        DenseGraph::contract_vertices(self, u, v);
+    }
+
+    fn union(&self, other: &Self) -> Self {
+        DenseGraph::union(self, other)
+    }
+
+    fn components(&self) -> Vec<Self> {
+        DenseGraph::components(self)
+    }
+
+    fn power(&self, k: u32) -> Self::Power {
+        DenseGraph::power(self, k)
+    }
+}
@@ -0,0 +1,76 @@
+//! Traits that abstract over the different graph representations.
+//!
+//! The local-consistency algorithms and the polymorphism search are written
+//! against these traits, so that they can be run on an [`AdjacencyList`] as well
+//! as on a [`DenseGraph`].
+//!
+//! [`AdjacencyList`]: ../adjacency_list/struct.AdjacencyList.html
+//! [`DenseGraph`]: ../dense_graph/struct.DenseGraph.html
+use crate::adjacency_list::VertexID;
+
+/// Read access to a directed graph.
+pub trait Graph {
+    /// The type that identifies a vertex of the graph.
+    type Vertex: VertexID;
+
+    /// Returns an iterator over references to all of the vertices in the graph.
+    fn vertices(&self) -> impl Iterator<Item = &Self::Vertex>;
+
+    /// Returns the number of vertices in the graph.
+    fn vertex_count(&self) -> usize;
+
+    /// Returns `true` if the graph contains the given vertex, false otherwise.
+    fn has_vertex(&self, v: &Self::Vertex) -> bool;
+
+    /// Returns an iterator over all of the edges in the graph.
+    fn edges(&self) -> impl Iterator<Item = (Self::Vertex, Self::Vertex)>;
+
+    /// Returns `true` if the graph contains the given edge, false otherwise.
+    fn has_edge(&self, u: &Self::Vertex, v: &Self::Vertex) -> bool;
+
+    /// Returns an iterator over the successors of the vertex `v`.
+    fn out_neighbors(&self, v: &Self::Vertex) -> impl Iterator<Item = Self::Vertex>;
+
+    /// Returns an iterator over the predecessors of the vertex `v`.
+    fn in_neighbors(&self, v: &Self::Vertex) -> impl Iterator<Item = Self::Vertex>;
+
+    /// Returns the total count of neighboring vertices of the vertex `v`.
+    fn degree(&self, v: &Self::Vertex) -> usize {
+        self.out_neighbors(v).count() + self.in_neighbors(v).count()
+    }
+}
+
+/// A graph that can be built up, combined and contracted. This is what the
+/// construction of indicator graphs needs.
+pub trait MutableGraph: Graph + Clone + Default {
+    /// The graph type that represents the k-ary product graph.
+    type Power: MutableGraph<Vertex = Vec<Self::Vertex>>;
+
+    /// Adds a vertex to the graph.
+    ///
+    /// If the graph did not have this vertex present, `true` is returned.
+    ///
+    /// If the graph did have this vertex present, `false` is returned.
+    fn add_vertex(&mut self, v: Self::Vertex) -> bool;
+
+    /// Adds an edge to the graph.
+    ///
+    /// If the graph did not have this edge present, `true` is returned.
+    ///
+    /// If the graph did have this edge present, `false` is returned.
+    fn add_edge(&mut self, u: &Self::Vertex, v: &Self::Vertex) -> bool;
+
+    /// Contracts the vertex `v` with the vertex `u` so that the resulting vertex
+    /// has id `u`.
+    fn contract_vertices(&mut self, u: &Self::Vertex, v: &Self::Vertex);
+
+    /// Performs the union of G and H, which is the graph with vertex set V(G) ∪
+    /// V(H).
+    fn union(&self, other: &Self) -> Self;
+
+    /// Returns a vector of the (weakly connected) components of the graph.
+    fn components(&self) -> Vec<Self>;
+
+    /// Returns the k-ary product graph.
+    fn power(&self, k: u32) -> Self::Power;
+}
@@ -0,0 +1,582 @@
+//! Reading graphs from files.
+//!
+//! The following formats are understood:
+//!
+//! - Edge lists, with one edge `u v` per line. A line with a single vertex adds
+//!   an isolated vertex, lines starting with `#` are comments.
+//! - A subset of the DOT language that covers what `AdjacencyList::to_dot`
+//!   writes: a `digraph` (or `graph`) whose statements are vertices `u` and
+//!   edge chains `u -> v -> w`, optionally with attribute lists `[...]` that are
+//!   ignored. Vertices are identifiers, numbers or quoted strings.
+//! - digraph6 and graph6, as described in the documentation of nauty. Only the
+//!   first graph of the file is read, a graph6 graph is read as a symmetric
+//!   digraph.
+//!
+//! Vertices are given arbitrary labels in the file. They are numbered in the
+//! order of their first appearance, and the labels are returned alongside the
+//! graph. The vertices of a digraph6 or graph6 graph are labeled `0` to `n-1`.
+use std::{collections::HashMap, error::Error, fmt, fs, io};
+
+use crate::{
+    adjacency_list::AdjacencyList,
+    consistency::{List, Lists},
+};
+
+/// A graph read from a file together with the labels of its vertices. The
+/// vertex `i` has label `labels[i]`.
+#[derive(Debug, Clone, Default)]
+pub struct LabeledGraph {
+    pub graph: AdjacencyList<u32>,
+    pub labels: Vec<String>,
+    ids: HashMap<String, u32>,
+}
+
+impl LabeledGraph {
+    /// Creates an empty `LabeledGraph`.
+    pub fn new() -> LabeledGraph {
+        LabeledGraph::default()
+    }
+
+    /// Returns the vertex with label `label`, if there is one.
+    pub fn id(&self, label: &str) -> Option<u32> {
+        self.ids.get(label).copied()
+    }
+
+    /// Returns the label of the vertex `v`.
+    pub fn label(&self, v: u32) -> &str {
+        &self.labels[v as usize]
+    }
+
+    /// Adds a vertex with label `label`, if there is none, and returns it.
+    pub fn add_vertex(&mut self, label: &str) -> u32 {
+        if let Some(v) = self.id(label) {
+            return v;
+        }
+        let v = self.labels.len() as u32;
+        self.labels.push(label.into());
+        self.ids.insert(label.into(), v);
+        self.graph.add_vertex(v);
+        v
+    }
+
+    /// Adds an edge between the vertices with labels `u` and `v`, which are
+    /// added if necessary.
+    pub fn add_edge(&mut self, u: &str, v: &str) {
+        let u = self.add_vertex(u);
+        let v = self.add_vertex(v);
+        self.graph.add_edge(&u, &v);
+    }
+
+    /// Returns `graph`, whose vertices are vertices of this graph, with the
+    /// labels of this graph.
+    pub fn relabel(&self, graph: AdjacencyList<u32>) -> LabeledGraph {
+        LabeledGraph {
+            graph,
+            labels: self.labels.clone(),
+            ids: self.ids.clone(),
+        }
+    }
+
+    /// Returns the edges of the graph as a comma-separated list of labels,
+    /// e.g. `a->b, c->b`.
+    pub fn edge_list(&self) -> String {
+        let mut edges = self.graph.edges().collect::<Vec<_>>();
+        edges.sort_unstable();
+        edges
+            .iter()
+            .map(|(u, v)| format!("{}->{}", self.label(*u), self.label(*v)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Labels the vertices `0..n` of a graph by their numbers, e.g. the vertices
+/// of a triad as in `Notation::EdgeList`.
+impl From<AdjacencyList<u32>> for LabeledGraph {
+    fn from(graph: AdjacencyList<u32>) -> Self {
+        let n = graph.vertices().map(|v| v + 1).max().unwrap_or(0);
+        let labels = (0..n).map(|v| v.to_string()).collect::<Vec<_>>();
+        let ids = labels.iter().cloned().zip(0..).collect::<HashMap<_, _>>();
+        LabeledGraph { graph, labels, ids }
+    }
+}
+
+/// The formats a graph file can be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphFormat {
+    EdgeList,
+    Dot,
+    Digraph6,
+    Graph6,
+}
+
+impl GraphFormat {
+    /// Guesses the format of a file from its path and its content.
+    pub fn detect(path: &str, content: &str) -> GraphFormat {
+        let content = content.trim_start();
+        let keyword = content
+            .split(|c: char| c.is_whitespace() || c == '{')
+            .next()
+            .unwrap_or("");
+        if path.ends_with(".d6") || content.starts_with(">>digraph6<<") || content.starts_with('&')
+        {
+            GraphFormat::Digraph6
+        } else if path.ends_with(".g6") || content.starts_with(">>graph6<<") {
+            GraphFormat::Graph6
+        } else if path.ends_with(".dot")
+            || path.ends_with(".gv")
+            || ["digraph", "graph", "strict"].contains(&keyword)
+        {
+            GraphFormat::Dot
+        } else {
+            GraphFormat::EdgeList
+        }
+    }
+}
+
+/// An error that occured while reading a graph file.
+#[derive(Debug)]
+pub enum GraphFileError {
+    /// The file could not be read
+    Io(io::Error),
+    /// The file is malformed at the given line and column, both starting at 1
+    Syntax {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+}
+
+impl GraphFileError {
+    fn syntax(line: usize, column: usize, message: impl Into<String>) -> GraphFileError {
+        GraphFileError::Syntax {
+            line,
+            column,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for GraphFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GraphFileError::Io(e) => write!(f, "{}", e),
+            GraphFileError::Syntax {
+                line,
+                column,
+                message,
+            } => write!(f, "{}:{}: {}", line, column, message),
+        }
+    }
+}
+
+impl Error for GraphFileError {}
+
+impl From<io::Error> for GraphFileError {
+    fn from(e: io::Error) -> Self {
+        GraphFileError::Io(e)
+    }
+}
+
+/// Reads the graph in the file at `path`, whose format is detected by
+/// [`GraphFormat::detect`](enum.GraphFormat.html#method.detect). Errors are
+/// prefixed by the path, syntax errors are reported as `io::Error` of kind
+/// `InvalidData`.
+pub fn read_graph(path: &str) -> io::Result<LabeledGraph> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path, e)))?;
+    parse_graph(&content, GraphFormat::detect(path, &content)).map_err(|e| match e {
+        GraphFileError::Io(e) => e,
+        e => io::Error::new(io::ErrorKind::InvalidData, format!("{}:{}", path, e)),
+    })
+}
+
+/// Parses a graph written in the format `format`.
+///
+/// # Examples
+///
+/// ```
+/// use tripolys::graph_file::{parse_graph, GraphFormat};
+///
+/// let graph = parse_graph("digraph {\n  a -> b -> c;\n}", GraphFormat::Dot).unwrap();
+/// assert_eq!(graph.edge_list(), "a->b, b->c");
+///
+/// // The directed 3-cycle
+/// let graph = parse_graph("&BP_", GraphFormat::Digraph6).unwrap();
+/// assert_eq!(graph.edge_list(), "0->1, 1->2, 2->0");
+///
+/// let err = parse_graph("a b\nc d e", GraphFormat::EdgeList).unwrap_err();
+/// assert_eq!(err.to_string(), "2:5: Expected one or two vertices per line");
+/// ```
+pub fn parse_graph(content: &str, format: GraphFormat) -> Result<LabeledGraph, GraphFileError> {
+    match format {
+        GraphFormat::EdgeList => parse_edge_list(content),
+        GraphFormat::Dot => parse_dot(content),
+        GraphFormat::Digraph6 => parse_graph6(content, true),
+        GraphFormat::Graph6 => parse_graph6(content, false),
+    }
+}
+
+// Returns the words of `line` together with their columns.
+fn words(line: &str) -> Vec<(usize, &str)> {
+    let mut words = Vec::new();
+    let mut start = None;
+    for (i, c) in line
+        .char_indices()
+        .chain(std::iter::once((line.len(), ' ')))
+    {
+        match (start, c.is_whitespace()) {
+            (None, false) => start = Some(i),
+            (Some(s), true) => {
+                words.push((line[..s].chars().count() + 1, &line[s..i]));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    words
+}
+
+fn parse_edge_list(content: &str) -> Result<LabeledGraph, GraphFileError> {
+    let mut graph = LabeledGraph::new();
+    for (i, line) in content.lines().enumerate() {
+        if line.trim_start().starts_with('#') {
+            continue;
+        }
+        match words(line).as_slice() {
+            [] => {}
+            [(_, v)] => {
+                graph.add_vertex(v);
+            }
+            [(_, u), (_, v)] => graph.add_edge(u, v),
+            [_, _, (column, _), ..] => {
+                return Err(GraphFileError::syntax(
+                    i + 1,
+                    *column,
+                    "Expected one or two vertices per line",
+                ))
+            }
+        }
+    }
+    Ok(graph)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    // An identifier, number or quoted string
+    Id(String),
+    Arrow,
+    Symbol(char),
+}
+
+// Splits DOT source into tokens with their line and column.
+fn tokenize(content: &str) -> Result<Vec<(usize, usize, Token)>, GraphFileError> {
+    let mut tokens = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        let chars = line.chars().collect::<Vec<_>>();
+        let mut j = 0;
+        while j < chars.len() {
+            let (line, column) = (i + 1, j + 1);
+            match chars[j] {
+                c if c.is_whitespace() => j += 1,
+                '#' if j == 0 => break,
+                '/' if chars.get(j + 1) == Some(&'/') => break,
+                '-' if matches!(chars.get(j + 1), Some('>') | Some('-')) => {
+                    tokens.push((line, column, Token::Arrow));
+                    j += 2;
+                }
+                '"' => {
+                    let mut s = String::new();
+                    j += 1;
+                    loop {
+                        match chars.get(j) {
+                            Some('"') => break,
+                            Some('\\') if chars.get(j + 1) == Some(&'"') => {
+                                s.push('"');
+                                j += 2;
+                            }
+                            Some(c) => {
+                                s.push(*c);
+                                j += 1;
+                            }
+                            None => {
+                                return Err(GraphFileError::syntax(
+                                    line,
+                                    column,
+                                    "Unterminated string",
+                                ))
+                            }
+                        }
+                    }
+                    tokens.push((line, column, Token::Id(s)));
+                    j += 1;
+                }
+                c if c.is_alphanumeric() || c == '_' || c == '.' || c == '-' => {
+                    let start = j;
+                    while j < chars.len()
+                        && (chars[j].is_alphanumeric() || chars[j] == '_' || chars[j] == '.')
+                    {
+                        j += 1;
+                    }
+                    // A leading '-' of a negative number
+                    if j == start {
+                        j += 1;
+                        while j < chars.len() && (chars[j].is_ascii_digit() || chars[j] == '.') {
+                            j += 1;
+                        }
+                    }
+                    let s = chars[start..j].iter().collect::<String>();
+                    tokens.push((line, column, Token::Id(s)));
+                }
+                c @ ('{' | '}' | '[' | ']' | ';' | ',' | '=') => {
+                    tokens.push((line, column, Token::Symbol(c)));
+                    j += 1;
+                }
+                c => {
+                    return Err(GraphFileError::syntax(
+                        line,
+                        column,
+                        format!("Unexpected character {:?}", c),
+                    ))
+                }
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+fn parse_dot(content: &str) -> Result<LabeledGraph, GraphFileError> {
+    let tokens = tokenize(content)?;
+    let end = {
+        let lines = content.lines().count().max(1);
+        let column = content.lines().last().map_or(0, |l| l.chars().count()) + 1;
+        (lines, column, Token::Symbol('\0'))
+    };
+    let mut tokens = tokens.iter().chain(std::iter::once(&end)).peekable();
+    let unexpected = |(line, column, token): &(usize, usize, Token), expected: &str| {
+        let found = match token {
+            Token::Id(s) => format!("{:?}", s),
+            Token::Arrow => "\"->\"".into(),
+            Token::Symbol('\0') => "end of file".into(),
+            Token::Symbol(c) => format!("{:?}", c),
+        };
+        GraphFileError::syntax(
+            *line,
+            *column,
+            format!("Expected {}, found {}", expected, found),
+        )
+    };
+
+    // Header: [strict] (digraph | graph) [ID] {
+    let mut next = tokens.next().unwrap();
+    if next.2 == Token::Id("strict".into()) {
+        next = tokens.next().unwrap();
+    }
+    // Edges of a graph are undirected
+    let directed = match &next.2 {
+        Token::Id(s) if s == "digraph" => true,
+        Token::Id(s) if s == "graph" => false,
+        _ => return Err(unexpected(next, "\"digraph\"")),
+    };
+    next = tokens.next().unwrap();
+    if let Token::Id(_) = next.2 {
+        next = tokens.next().unwrap();
+    }
+    if next.2 != Token::Symbol('{') {
+        return Err(unexpected(next, "'{'"));
+    }
+
+    let mut graph = LabeledGraph::new();
+    loop {
+        let next = tokens.next().unwrap();
+        let id = match &next.2 {
+            Token::Symbol('}') => break,
+            Token::Symbol(';') => continue,
+            Token::Id(id) => id,
+            _ => return Err(unexpected(next, "a vertex or '}'")),
+        };
+        // Graph attributes like `rankdir = LR`
+        if tokens.peek().map(|t| &t.2) == Some(&Token::Symbol('=')) {
+            tokens.next();
+            match tokens.next().unwrap() {
+                (_, _, Token::Id(_)) => continue,
+                t => return Err(unexpected(t, "a value")),
+            }
+        }
+        if ["node", "edge", "graph"].contains(&id.as_str()) {
+            // Default attributes of vertices or edges
+        } else {
+            let mut u = graph.add_vertex(id);
+            while tokens.peek().map(|t| &t.2) == Some(&Token::Arrow) {
+                tokens.next();
+                match tokens.next().unwrap() {
+                    (_, _, Token::Id(id)) => {
+                        let v = graph.add_vertex(id);
+                        graph.graph.add_edge(&u, &v);
+                        if !directed {
+                            graph.graph.add_edge(&v, &u);
+                        }
+                        u = v;
+                    }
+                    t => return Err(unexpected(t, "a vertex")),
+                }
+            }
+        }
+        // Attribute list, which is ignored
+        if tokens.peek().map(|t| &t.2) == Some(&Token::Symbol('[')) {
+            loop {
+                match tokens.next().unwrap() {
+                    (_, _, Token::Symbol(']')) => break,
+                    (_, _, Token::Symbol('\0')) => return Err(unexpected(&end, "']'")),
+                    _ => {}
+                }
+            }
+        }
+    }
+    Ok(graph)
+}
+
+// Parses digraph6 (`directed`) or graph6. Columns count bytes of the line.
+fn parse_graph6(content: &str, directed: bool) -> Result<LabeledGraph, GraphFileError> {
+    let (line, text) = content
+        .lines()
+        .enumerate()
+        .find(|(_, l)| !l.trim().is_empty())
+        .ok_or_else(|| GraphFileError::syntax(1, 1, "Empty file"))?;
+    let line = line + 1;
+    let header = if directed {
+        ">>digraph6<<"
+    } else {
+        ">>graph6<<"
+    };
+    let text = text.trim_end();
+    let (mut pos, bytes) = match text.strip_prefix(header) {
+        Some(rest) => (header.len(), rest.as_bytes()),
+        None => (0, text.as_bytes()),
+    };
+    let mut bytes = bytes.iter().peekable();
+    if directed {
+        match bytes.next() {
+            Some(b'&') => pos += 1,
+            _ => return Err(GraphFileError::syntax(line, pos + 1, "Expected '&'")),
+        }
+    }
+
+    let mut next = |pos: &mut usize| -> Result<u64, GraphFileError> {
+        *pos += 1;
+        match bytes.next() {
+            Some(&b) if (63..=126).contains(&b) => Ok(u64::from(b - 63)),
+            Some(&b) => Err(GraphFileError::syntax(
+                line,
+                *pos,
+                format!("Invalid character {:?}", b as char),
+            )),
+            None => Err(GraphFileError::syntax(
+                line,
+                *pos,
+                "Unexpected end of graph",
+            )),
+        }
+    };
+
+    // N(n) is 1, 4 or 8 bytes long
+    let mut n = next(&mut pos)?;
+    if n == 63 {
+        n = next(&mut pos)?;
+        let rest = if n == 63 {
+            n = 0;
+            6
+        } else {
+            2
+        };
+        for _ in 0..rest {
+            n = (n << 6) | next(&mut pos)?;
+        }
+    }
+
+    // The edges take ceil(n^2/6) bytes for digraph6 and ceil(n(n-1)/12) bytes
+    // for graph6, checked before n vertices are added
+    let n2 = u128::from(n) * u128::from(n);
+    let expected = if directed {
+        n2.div_ceil(6)
+    } else {
+        (n2 - u128::from(n)).div_ceil(12)
+    };
+    let found = text.len() - pos;
+    if expected != found as u128 {
+        return Err(GraphFileError::syntax(
+            line,
+            pos + 1,
+            format!("Expected {} bytes of edges for {} vertices, found {}", expected, n, found),
+        ));
+    }
+
+    let mut graph = LabeledGraph::new();
+    for v in 0..n {
+        graph.add_vertex(&v.to_string());
+    }
+    let pairs: Box<dyn Iterator<Item = (u64, u64)>> = if directed {
+        Box::new((0..n).flat_map(move |i| (0..n).map(move |j| (i, j))))
+    } else {
+        Box::new((1..n).flat_map(|j| (0..j).map(move |i| (i, j))))
+    };
+    let mut bits = 0;
+    let mut word = 0;
+    for (i, j) in pairs {
+        if bits == 0 {
+            word = next(&mut pos)?;
+            bits = 6;
+        }
+        bits -= 1;
+        if word & (1 << bits) != 0 {
+            graph.graph.add_edge(&(i as u32), &(j as u32));
+            if !directed {
+                graph.graph.add_edge(&(j as u32), &(i as u32));
+            }
+        }
+    }
+    Ok(graph)
+}
+
+/// Reads lists for a homomorphism from `g` to `h` from the file at `path`. Each
+/// line `v: a b c` restricts the image of the vertex `v` of `g` to the vertices
+/// `a`, `b` and `c` of `h`, where vertices are given by their labels.
+pub fn read_lists(path: &str, g: &LabeledGraph, h: &LabeledGraph) -> io::Result<Lists<u32, u32>> {
+    let content = fs::read_to_string(path)?;
+    let error = |line: usize, column: usize, msg: String| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}:{}:{}: {}", path, line, column, msg),
+        )
+    };
+
+    let mut lists = Lists::new();
+    for (i, line) in content.lines().enumerate() {
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+        let colon = line
+            .find(':')
+            .ok_or_else(|| error(i + 1, line.len() + 1, "Expected ':'".into()))?;
+        let v = line[..colon].trim();
+        let v = g.id(v).ok_or_else(|| {
+            let column = line.len() - line.trim_start().len() + 1;
+            error(i + 1, column, format!("Unknown instance vertex {:?}", v))
+        })?;
+        let offset = line[..=colon].chars().count();
+        let list = words(&line[colon + 1..])
+            .into_iter()
+            .map(|(column, a)| {
+                h.id(a).ok_or_else(|| {
+                    error(
+                        i + 1,
+                        offset + column,
+                        format!("Unknown template vertex {:?}", a),
+                    )
+                })
+            })
+            .collect::<Result<List<_>, _>>()?;
+        lists.insert(v, list);
+    }
+    Ok(lists)
+}
@@ -0,0 +1,353 @@
+//! Systems of height 1 identities, e.g. `f(x,y)=f(y,x)`, given as text.
+//!
+//! An identity of height 1 equates terms that apply an operation symbol to
+//! variables. A polymorphism satisfies it, if both sides are equal for every
+//! assignment of the variables. So the search contracts all tuples of the
+//! indicator graph that the terms of an identity evaluate to under the same
+//! assignment.
+//!
+//! A system can use several operation symbols, e.g. the Jónsson terms
+//! `d0(x,y,z)=x; d0(x,x,z)=d1(x,x,z); d1(x,y,x)=x; d1(x,z,z)=d2(x,z,z);
+//! d2(x,y,z)=z`. Each symbol has its own block of tuples in the indicator
+//! graph, and a side that is a single variable pins the value of the tuples.
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::{self, Display},
+    str::FromStr,
+};
+
+/// A side of an identity, i.e. an operation symbol applied to variables, e.g.
+/// `f(x,y,x)`, or a single variable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Term {
+    /// An operation symbol applied to variables
+    Operation(String, Vec<String>),
+    /// A single variable
+    Variable(String),
+}
+
+impl Term {
+    fn variables(&self) -> &[String] {
+        match self {
+            Term::Operation(_, variables) => variables,
+            Term::Variable(x) => std::slice::from_ref(x),
+        }
+    }
+}
+
+impl Display for Term {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Term::Operation(symbol, variables) => write!(f, "{}({})", symbol, variables.join(",")),
+            Term::Variable(x) => write!(f, "{}", x),
+        }
+    }
+}
+
+/// A set of tuples that a polymorphism has to map to the same value. The
+/// tuples are given with the index of their operation symbol.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Class {
+    pub tuples: Vec<(usize, Vec<u32>)>,
+    /// The values the class is pinned to by sides that are single variables.
+    /// If there are several, no polymorphism satisfies the identities.
+    pub values: Vec<u32>,
+}
+
+/// A system of height 1 identities.
+///
+/// The identities are separated by `;` or commas outside of the terms, and an
+/// identity may equate more than two terms, e.g.
+/// `f(x,x,y)=f(x,y,x)=f(y,x,x)`. A side may also be a single variable, as long
+/// as the identity contains a term. Whitespace is ignored.
+///
+/// # Example
+///
+/// ```
+/// use tripolys::identity::Identities;
+///
+/// let commutative: Identities = "f(x,y)=f(y,x)".parse().unwrap();
+/// assert_eq!(commutative.arities(), vec![2]);
+/// // the classes {(0,1), (1,0)}, {(0,2), (2,0)} and {(1,2), (2,1)}
+/// assert_eq!(commutative.contractions(3).len(), 3);
+///
+/// let majority: Identities = "m(x,x,y)=m(x,y,x)=m(y,x,x)=x".parse().unwrap();
+/// // the class of (0,0,0) is pinned to 0
+/// assert_eq!(majority.contractions(2)[0].values, vec![0]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identities {
+    identities: Vec<Vec<Term>>,
+    // The operation symbols with their arities in order of appearance
+    symbols: Vec<(String, u32)>,
+}
+
+impl Identities {
+    /// Returns the operation symbols in order of appearance.
+    pub fn symbols(&self) -> impl Iterator<Item = &str> {
+        self.symbols.iter().map(|(symbol, _)| symbol.as_str())
+    }
+
+    /// Returns the arities of the operation symbols in order of appearance.
+    pub fn arities(&self) -> Vec<u32> {
+        self.symbols.iter().map(|&(_, k)| k).collect()
+    }
+
+    /// Returns the classes of tuples that should be contracted when searching
+    /// for polymorphisms that satisfy the identities on a graph with `num`
+    /// nodes. The classes are disjoint and contain at least two tuples or are
+    /// pinned to a value.
+    pub fn contractions(&self, num: u32) -> Vec<Class> {
+        let mut tuples = Vec::<(usize, Vec<u32>)>::new();
+        let mut indices = HashMap::<(usize, Vec<u32>), usize>::new();
+        let mut parent = Vec::<usize>::new();
+        let mut pins = Vec::<(usize, u32)>::new();
+        for identity in &self.identities {
+            let mut variables = Vec::<&str>::new();
+            for x in identity.iter().flat_map(|term| term.variables()) {
+                if !variables.contains(&x.as_str()) {
+                    variables.push(x);
+                }
+            }
+            let position = |x: &String| variables.iter().position(|y| x == y).unwrap();
+            let terms = identity
+                .iter()
+                .map(|term| match term {
+                    Term::Operation(symbol, xs) => {
+                        let f = self.symbols.iter().position(|(g, _)| g == symbol).unwrap();
+                        (Some(f), xs.iter().map(position).collect::<Vec<_>>())
+                    }
+                    Term::Variable(x) => (None, vec![position(x)]),
+                })
+                .collect::<Vec<_>>();
+
+            // Evaluate the terms under every assignment of the variables
+            let mut assignment = vec![0; variables.len()];
+            loop {
+                let mut first = None;
+                let mut values = Vec::new();
+                for (f, positions) in &terms {
+                    let tuple = positions.iter().map(|&i| assignment[i]).collect::<Vec<_>>();
+                    let f = match *f {
+                        Some(f) => f,
+                        None => {
+                            values.push(tuple[0]);
+                            continue;
+                        }
+                    };
+                    let i = *indices.entry((f, tuple.clone())).or_insert_with(|| {
+                        tuples.push((f, tuple));
+                        parent.push(parent.len());
+                        parent.len() - 1
+                    });
+                    match first {
+                        None => first = Some(i),
+                        Some(j) => union(&mut parent, j, i),
+                    }
+                }
+                let first = first.unwrap();
+                pins.extend(values.into_iter().map(|a| (first, a)));
+                match assignment.iter().rposition(|&a| a + 1 < num) {
+                    Some(i) => {
+                        assignment[i] += 1;
+                        for a in &mut assignment[i + 1..] {
+                            *a = 0;
+                        }
+                    }
+                    None => break,
+                }
+            }
+        }
+
+        // The classes in the order of their first tuple
+        let mut classes = Vec::<Class>::new();
+        let mut class = HashMap::<usize, usize>::new();
+        for (i, tuple) in tuples.into_iter().enumerate() {
+            let root = find(&mut parent, i);
+            let c = *class.entry(root).or_insert_with(|| {
+                classes.push(Class { tuples: Vec::new(), values: Vec::new() });
+                classes.len() - 1
+            });
+            classes[c].tuples.push(tuple);
+        }
+        for (i, a) in pins {
+            let values = &mut classes[class[&find(&mut parent, i)]].values;
+            if !values.contains(&a) {
+                values.push(a);
+            }
+        }
+        classes.retain(|class| class.tuples.len() > 1 || !class.values.is_empty());
+        classes
+    }
+}
+
+fn find(parent: &mut [usize], mut i: usize) -> usize {
+    while parent[i] != i {
+        parent[i] = parent[parent[i]];
+        i = parent[i];
+    }
+    i
+}
+
+// Merges the classes of i and j, the class of i keeps its root.
+fn union(parent: &mut [usize], i: usize, j: usize) {
+    let (ri, rj) = (find(parent, i), find(parent, j));
+    parent[rj] = ri;
+}
+
+impl Display for Identities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let identities = self
+            .identities
+            .iter()
+            .map(|identity| {
+                let terms = identity.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+                terms.join("=")
+            })
+            .collect::<Vec<_>>();
+        write!(f, "{}", identities.join(";"))
+    }
+}
+
+/// An error which can be returned when parsing identities.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseIdentityError {
+    /// The string is empty
+    Empty,
+    /// Something else was found at the given position
+    Expected(&'static str, usize),
+    /// An identity only equates variables
+    Height(String),
+    /// An operation symbol is used with different arities
+    Arity(String),
+}
+
+impl Display for ParseIdentityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseIdentityError::Empty => write!(f, "Empty identities"),
+            ParseIdentityError::Expected(what, i) => {
+                write!(f, "Expected {} at position {}", what, i)
+            }
+            ParseIdentityError::Height(identity) => {
+                write!(f, "Identity {} only equates variables", identity)
+            }
+            ParseIdentityError::Arity(symbol) => {
+                write!(f, "Symbol {} is used with different arities", symbol)
+            }
+        }
+    }
+}
+
+impl Error for ParseIdentityError {}
+
+// A recursive descent parser over the characters of the identities.
+struct Parser<'a> {
+    s: &'a str,
+    i: usize,
+}
+
+impl<'a> Parser<'a> {
+    // Returns the next character that is not whitespace.
+    fn peek(&mut self) -> Option<char> {
+        let rest = &self.s[self.i..];
+        self.i += rest.len() - rest.trim_start().len();
+        self.s[self.i..].chars().next()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.i += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char, what: &'static str) -> Result<(), ParseIdentityError> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(ParseIdentityError::Expected(what, self.i))
+        }
+    }
+
+    fn name(&mut self, what: &'static str) -> Result<String, ParseIdentityError> {
+        self.peek();
+        let rest = &self.s[self.i..];
+        let len = rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        if len == 0 {
+            return Err(ParseIdentityError::Expected(what, self.i));
+        }
+        self.i += len;
+        Ok(rest[..len].to_string())
+    }
+
+    fn term(&mut self) -> Result<Term, ParseIdentityError> {
+        let name = self.name("a term")?;
+        if !self.eat('(') {
+            return Ok(Term::Variable(name));
+        }
+        let mut variables = vec![self.name("a variable")?];
+        while self.eat(',') {
+            variables.push(self.name("a variable")?);
+        }
+        self.expect(')', "')'")?;
+        Ok(Term::Operation(name, variables))
+    }
+
+    fn identity(&mut self) -> Result<Vec<Term>, ParseIdentityError> {
+        let mut terms = vec![self.term()?];
+        self.expect('=', "'='")?;
+        terms.push(self.term()?);
+        while self.eat('=') {
+            terms.push(self.term()?);
+        }
+        if terms.iter().all(|term| matches!(term, Term::Variable(_))) {
+            let terms = terms.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+            return Err(ParseIdentityError::Height(terms.join("=")));
+        }
+        Ok(terms)
+    }
+}
+
+/// Parses a system of height 1 identities, e.g. `f(x,y)=f(y,x)` or
+/// `p(x,y,y)=x; p(x,x,y)=y`.
+impl FromStr for Identities {
+    type Err = ParseIdentityError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser { s, i: 0 };
+        if parser.peek().is_none() {
+            return Err(ParseIdentityError::Empty);
+        }
+        let mut identities = vec![parser.identity()?];
+        while parser.eat(';') || parser.eat(',') {
+            identities.push(parser.identity()?);
+        }
+        if parser.peek().is_some() {
+            return Err(ParseIdentityError::Expected("';' or the end", parser.i));
+        }
+
+        let mut symbols = Vec::<(String, u32)>::new();
+        for term in identities.iter().flatten() {
+            if let Term::Operation(symbol, variables) = term {
+                let k = variables.len() as u32;
+                match symbols.iter().find(|(g, _)| g == symbol) {
+                    Some(&(_, l)) if k != l => {
+                        return Err(ParseIdentityError::Arity(symbol.clone()));
+                    }
+                    Some(_) => {}
+                    None => symbols.push((symbol.clone(), k)),
+                }
+            }
+        }
+        Ok(Identities {
+            identities,
+            symbols,
+        })
+    }
+}
@@ -21,10 +21,18 @@
 #![allow(clippy::redundant_closure_for_method_calls)]
 #![allow(clippy::cast_precision_loss)]
 #![allow(clippy::use_self)]
+#![allow(clippy::upper_case_acronyms)]
 
 pub mod adjacency_list;
+pub mod cache_file;
 pub mod configuration;
 pub mod consistency;
+pub mod dense_graph;
+pub mod graph;
+pub mod graph_file;
+pub mod identity;
 pub mod metrics;
 pub mod polymorphism;
+pub mod power_graph;
+pub mod search;
 pub mod triad;
@@ -13,28 +13,214 @@
 
 use colored::*;
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
-use std::{fs::File, io, sync::Mutex};
+use std::{convert::TryFrom, fmt::Display, fs::File, io, sync::Mutex};
 use tripolys::{
     adjacency_list::AdjacencyList,
-    configuration::{Constraint, Globals, Run, TripolysOptions},
-    metrics::SearchLog,
-    polymorphism::PolymorphismSearcher,
-    triad::{cores_length_range, cores_nodes_range, FileParser},
+    configuration::{Backend, Constraint, Globals, Run, Template, TripolysOptions},
+    consistency::Lists,
+    dense_graph::DenseGraph,
+    graph_file::{read_graph, read_lists, LabeledGraph},
+    metrics::{CountingAllocator, Metrics, SearchLog},
+    polymorphism::{
+        width_1, PolymorphismConfiguration, PolymorphismSearcher, MAX_WIDTH_1_PAIRS,
+    },
+    search::{automorphisms, Search},
+    triad::{
+        convert_caches, cores_length_range, cores_nodes_range, triads_length_range,
+        triads_nodes_range, FileParser, Triad,
+    },
 };
 
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
 /// Print error message to stderr and terminate
 fn error(message: &str) -> ! {
     eprintln!("{} {}", "error:".red(), message);
     std::process::exit(1);
 }
 
+/// Returns the searcher for the polymorphism `config` with the search settings
+/// of `options`
+fn searcher(config: &PolymorphismConfiguration, options: &TripolysOptions) -> PolymorphismSearcher {
+    let searcher = PolymorphismSearcher::get(config)
+        .consistency(options.consistency)
+        .backtracking(options.backtracking)
+        .restarts(options.restarts)
+        .parallelism(options.parallelism)
+        .variable_order(options.variable_order)
+        .value_order(options.value_order)
+        .count(options.count)
+        .modulo_automorphisms(options.modulo_automorphisms);
+    let searcher = match options.limit {
+        Some(limit) => searcher.limit(limit),
+        None => searcher,
+    };
+    match options.seed {
+        Some(seed) => searcher.seed(seed),
+        None => searcher,
+    }
+}
+
+/// Returns the search for a homomorphism from `g` to `h` on the lists `lists`
+/// with the search settings of `options`
+fn hom_search(
+    g: &AdjacencyList<u32>,
+    h: &AdjacencyList<u32>,
+    lists: &Lists<u32, u32>,
+    options: &TripolysOptions,
+) -> Search<u32, u32> {
+    let search = Search::new(g, h, lists)
+        .propagation(options.consistency.propagation)
+        .backtracking(options.backtracking)
+        .restarts(options.restarts)
+        .variable_order(options.variable_order);
+    let search = match options.seed {
+        Some(seed) => search.seed(seed),
+        None => search,
+    };
+    if options.count && options.modulo_automorphisms {
+        search.modulo(&automorphisms(h))
+    } else {
+        search
+    }
+}
+
+/// Searches for the polymorphism `config` of `triad` on the graph
+/// representation and with the search settings of `options`
+fn search(triad: &Triad, config: &PolymorphismConfiguration, options: &TripolysOptions) -> Metrics {
+    let searcher = searcher(config, options);
+    match options.backend {
+        Backend::AdjacencyList => searcher.search(&AdjacencyList::from(triad)),
+        Backend::Dense => searcher.search(&DenseGraph::from(triad)),
+        Backend::Implicit => searcher.implicit(true).search(&DenseGraph::from(triad)),
+    }
+}
+
+/// Searches for the polymorphism `config` of `graph` on the graph
+/// representation and with the search settings of `options`
+fn search_graph(
+    graph: &AdjacencyList<u32>,
+    config: &PolymorphismConfiguration,
+    options: &TripolysOptions,
+) -> Metrics {
+    let searcher = searcher(config, options);
+    match options.backend {
+        Backend::AdjacencyList => searcher.search(graph),
+        Backend::Dense => searcher.search(&DenseGraph::from(graph)),
+        Backend::Implicit => searcher.implicit(true).search(&DenseGraph::from(graph)),
+    }
+}
+
+/// Prints whether `graph` is a core, otherwise its core and the retraction
+/// onto it
+fn print_core(name: &dyn Display, graph: &LabeledGraph) {
+    let (core, retraction) = graph.graph.core();
+    if core.vertices().count() == graph.graph.vertices().count() {
+        println!("{}", format!("✔ {} is a core!", name).green());
+        return;
+    }
+    println!("{}", format!("✘ {} is not a core!", name).red());
+    match Triad::try_from(core.clone()) {
+        Ok(core) => println!("Core: {}", core),
+        Err(_) => println!("Core: {}", graph.relabel(core).edge_list()),
+    }
+    let mut retraction = retraction.into_iter().collect::<Vec<_>>();
+    retraction.sort_unstable();
+    let retraction = retraction
+        .iter()
+        .map(|(v, w)| format!("{}->{}", graph.label(*v), graph.label(*w)))
+        .collect::<Vec<_>>();
+    println!("Retraction: {}", retraction.join(", "));
+}
+
+/// Prints whether arc consistency solves the CSP of `graph`, i.e. whether
+/// U(H) -> H for H = `graph`
+fn print_width_1(name: &dyn Display, graph: &AdjacencyList<u32>) {
+    let metrics = match width_1(graph) {
+        Ok(metrics) => metrics,
+        Err(e) => error(&format!("{} (at most {})", e, MAX_WIDTH_1_PAIRS)),
+    };
+    if metrics.polymorphism.is_some() {
+        println!("{}", format!("✔ AC solves CSP({})!", name).green());
+    } else {
+        println!("{}", format!("✘ AC does not solve CSP({})!", name).red());
+    }
+    println!("indicator_time: {:?}", metrics.indicator_time);
+    println!("ac_time: {:?}", metrics.ac_time);
+    println!("search_time: {:?}", metrics.search_time);
+}
+
+/// Checks the polymorphism `polymorphism` for the triad, graph, list or range
+/// of triads of `options`
+fn check(polymorphism: &PolymorphismConfiguration, options: &TripolysOptions) -> io::Result<()> {
+    if let Some(ref triad) = options.triad {
+        println!("\n> Checking polymorphism...");
+        search(triad, polymorphism, options).print_console(polymorphism, triad)?;
+    } else if let Some(path) = &options.graph {
+        println!("\n> Checking polymorphism...");
+        let graph = read_graph(path)?.graph;
+        search_graph(&graph, polymorphism, options).print_console(polymorphism, path)?;
+    } else if let Some(path) = &options.list {
+        let triads = FileParser::read_triads(path)?;
+        let log = Mutex::new(SearchLog::new(format!(
+            "{}/results/lists/{}_{}.csv",
+            Globals::get().data,
+            polymorphism,
+            path
+        )));
+
+        triads.par_iter().for_each(|triad| {
+            let res = search(triad, polymorphism, options);
+            log.lock().unwrap().add(triad.clone(), res);
+        });
+        log.lock().unwrap().write()?;
+    } else if let Some(constraint) = &options.constraint {
+        let range = options.range.as_ref().unwrap();
+
+        println!("> Generating triads...");
+        let triads = match (constraint, options.all) {
+            (Constraint::Length, false) => cores_length_range(range.clone()),
+            (Constraint::Nodes, false) => cores_nodes_range(range.clone()),
+            (Constraint::Length, true) => triads_length_range(range.clone()),
+            (Constraint::Nodes, true) => triads_nodes_range(range.clone()),
+        };
+        println!("{}", "\t✔ Generated triads!".green());
+
+        for (i, vec) in triads.iter().enumerate() {
+            let log = Mutex::new(SearchLog::new(format!(
+                "{}//results/{}/{}_{}{}.csv",
+                Globals::get().data,
+                options.constraint.as_ref().unwrap(),
+                polymorphism,
+                range.start() + i as u32,
+                if options.all { "_all" } else { "" }
+            )));
+
+            println!(
+                "> Checking polymorphism for triads with {} {}...",
+                constraint.identity(),
+                range.start() + i as u32
+            );
+            vec.par_iter().for_each(|triad| {
+                let res = search(triad, polymorphism, options);
+                log.lock().unwrap().add(triad.clone(), res);
+            });
+            log.lock().unwrap().write()?;
+        }
+    }
+    Ok(())
+}
+
 /// Runs the program based on the given configuration and options
 fn run(options: TripolysOptions) -> io::Result<()> {
     match options.run {
         Run::Dot => {
+            let mut f = File::create(options.dot.unwrap()).unwrap();
             if let Some(triad) = &options.triad {
-                let mut f = File::create(&options.dot.unwrap()).unwrap();
                 AdjacencyList::<u32>::from(triad).to_dot(&mut f);
+            } else if let Some(path) = &options.graph {
+                read_graph(path)?.graph.to_dot(&mut f);
             }
         }
 
@@ -43,69 +229,81 @@ fn run(options: TripolysOptions) -> io::Result<()> {
                 if triad.is_core() {
                     println!("{}", format!("✔ {} is a core!", triad).green());
                 } else {
-                    println!("{}", format!("✘ {} is not a core!", triad).red());
+                    print_core(triad, &LabeledGraph::from(AdjacencyList::from(triad)));
                 }
+            } else if let Some(path) = &options.graph {
+                print_core(path, &read_graph(path)?);
             }
         }
 
-        Run::Polymorphism => {
-            if let Some(polymorphism) = &options.polymorphism_config {
-                if let Some(ref triad) = options.triad {
-                    println!("\n> Checking polymorphism...");
-                    PolymorphismSearcher::get(polymorphism)
-                        .search(&triad.into())
-                        .print_console(polymorphism, triad)?;
-                } else if let Some(path) = &options.list {
-                    let triads = FileParser::read_triads(path)?;
-                    let log = Mutex::new(SearchLog::new(format!(
-                        "{}/results/lists/{}_{}.csv",
-                        Globals::get().data,
-                        options.polymorphism_config.as_ref().unwrap(),
-                        path
-                    )));
-
-                    triads.par_iter().for_each(|triad| {
-                        let res = PolymorphismSearcher::get(
-                            &options.polymorphism_config.as_ref().unwrap(),
-                        )
-                        .search(&triad.into());
-                        log.lock().unwrap().add(triad.clone(), res);
-                    });
-                    log.lock().unwrap().write()?;
-                } else if let Some(constraint) = &options.constraint {
-                    let range = options.range.as_ref().unwrap();
-
-                    println!("> Generating triads...");
-                    let triads = match constraint {
-                        Constraint::Length => cores_length_range(range.clone()),
-                        Constraint::Nodes => cores_nodes_range(range.clone()),
-                    };
-                    println!("{}", "\t✔ Generated triads!".green());
-
-                    for (i, vec) in triads.iter().enumerate() {
-                        let log = Mutex::new(SearchLog::new(format!(
-                            "{}//results/{}/{}_{}.csv",
-                            Globals::get().data,
-                            options.constraint.as_ref().unwrap(),
-                            options.polymorphism_config.as_ref().unwrap(),
-                            range.start() + i as u32
-                        )));
-
-                        println!(
-                            "> Checking polymorphism for triads with {} {}...",
-                            constraint.identity(),
-                            range.start() + i as u32
-                        );
-                        vec.par_iter().for_each(|triad| {
-                            let res = PolymorphismSearcher::get(
-                                &options.polymorphism_config.as_ref().unwrap(),
-                            )
-                            .search(&triad.into());
-                            log.lock().unwrap().add(triad.clone(), res);
-                        });
-                        log.lock().unwrap().write()?;
+        Run::Width1 => {
+            if let Some(triad) = &options.triad {
+                print_width_1(triad, &AdjacencyList::from(triad));
+            } else if let Some(path) = &options.graph {
+                print_width_1(path, &read_graph(path)?.graph);
+            }
+        }
+
+        Run::Hom => {
+            let hom = options.hom.as_ref().unwrap();
+            let g = read_graph(&hom.instance)?;
+            let h = match &hom.template {
+                Template::Triad(triad) => LabeledGraph::from(AdjacencyList::from(triad)),
+                Template::File(path) => read_graph(path)?,
+            };
+            let lists = match &hom.lists {
+                Some(path) => read_lists(path, &g, &h)?,
+                None => Lists::new(),
+            };
+
+            let mut metrics = Metrics::default();
+            let lists = match options.consistency.preprocessing.apply(&g.graph, &h.graph, lists) {
+                Some(lists) => lists,
+                None if options.count => {
+                    println!("0");
+                    return Ok(());
+                }
+                None => {
+                    println!("{}", "no".red());
+                    return Ok(());
+                }
+            };
+            let mut search = hom_search(&g.graph, &h.graph, &lists, &options);
+            if options.count {
+                println!("{}", search.count(options.limit, &mut metrics));
+                return Ok(());
+            }
+            match search.run_with(options.parallelism, &mut metrics) {
+                Some(f) => {
+                    println!("{}", "yes".green());
+                    for v in 0..g.labels.len() as u32 {
+                        let w = f.get(&v).unwrap().iter().next().unwrap();
+                        println!("{} -> {}", g.label(v), h.label(w));
                     }
                 }
+                None => println!("{}", "no".red()),
+            }
+        }
+
+        Run::Convert => {
+            let format = options.convert.unwrap();
+            let data = &Globals::get().data;
+            let count = convert_caches(data, format, options.import_legacy)?;
+            println!(
+                "{}",
+                format!("✔ Converted {} cache files in {} to {}!", count, data, format).green()
+            );
+        }
+
+        Run::Polymorphism => {
+            if let Some(polymorphism) = &options.polymorphism_config {
+                check(polymorphism, &options)?;
+            }
+        }
+
+        Run::Cyclic => {
+            for polymorphism in &options.cyclic {
+                check(polymorphism, &options)?;
             }
         }
     }
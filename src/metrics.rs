@@ -1,4 +1,7 @@
 use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    fmt::Display,
     fs::OpenOptions,
     io::{Error, Write},
     time::Duration,
@@ -7,6 +10,7 @@ use std::{
 use colored::Colorize;
 
 use crate::{
+    consistency::Consistency,
     polymorphism::{Polymorphism, PolymorphismConfiguration},
     triad::Triad,
 };
@@ -25,7 +29,10 @@ impl SearchLog {
         }
     }
 
-    pub fn add(&mut self, triad: Triad, metrics: Metrics) {
+    /// Logs the metrics of the search on `triad`, which is recorded in
+    /// canonical form.
+    pub fn add(&mut self, mut triad: Triad, metrics: Metrics) {
+        triad.canonicalize();
         self.log.push((triad, metrics));
     }
 
@@ -37,9 +44,24 @@ impl SearchLog {
         {
             writeln!(
                 file,
-                "triad,polymorphism,backtracked,indicator_time,ac_time,search_time,total_time",
+                "triad,polymorphism,backtracked,indicator_time,ac_time,search_time,total_time,\
+                 steps,allocations,consistency,jumps,nogoods,restarts,solutions",
             )?;
-            for (triad, metrics) in &self.log {
+            // Sorted by triad and without duplicates, so that logs can be joined.
+            // Results that disagree on a triad are all kept and reported.
+            let mut log = self.log.iter().collect::<Vec<_>>();
+            log.sort_by(|a, b| a.0.cmp(&b.0));
+            log.dedup_by(|a, b| {
+                if a.0 != b.0 {
+                    return false;
+                }
+                let agree = (a.1.found(), a.1.solutions) == (b.1.found(), b.1.solutions);
+                if !agree {
+                    eprintln!("Conflicting results for triad {} in {}", a.0, self.path);
+                }
+                agree
+            });
+            for (triad, metrics) in log {
                 writeln!(file, "{},{}", triad, metrics.format())?;
             }
         }
@@ -52,30 +74,66 @@ impl SearchLog {
 #[derive(Debug, Default)]
 pub struct Metrics {
     pub backtracked: u32,
+    /// The number of assignments tried during the search.
+    pub steps: u64,
+    /// The number of backtracks that skipped at least one assignment.
+    pub jumps: u64,
+    /// The number of nogoods learned during the search.
+    pub nogoods: u64,
+    /// The number of restarts of the search.
+    pub restarts: u64,
+    /// The number of solutions, if the search counted them.
+    pub solutions: Option<u64>,
+    /// The number of heap allocations during the search, only counted if
+    /// `CountingAllocator` is the global allocator. Parallel searches sum the
+    /// allocations of all their workers.
+    pub allocations: u64,
+    /// The consistencies of the search.
+    pub consistency: Consistency,
     pub indicator_time: Duration,
     pub ac_time: Duration,
     pub search_time: Duration,
     pub total_time: Duration,
     pub polymorphism: Option<Polymorphism<u32>>,
+    /// The table of each operation symbol, if the identities have several.
+    pub polymorphisms: Vec<Polymorphism<u32>>,
 }
 
 impl Metrics {
     pub fn new() -> Metrics {
         Metrics {
             backtracked: 0,
+            steps: 0,
+            jumps: 0,
+            nogoods: 0,
+            restarts: 0,
+            solutions: None,
+            allocations: 0,
+            consistency: Consistency::default(),
             indicator_time: Duration::default(),
             ac_time: Duration::default(),
             search_time: Duration::default(),
             total_time: Duration::default(),
             polymorphism: None,
+            polymorphisms: Vec::new(),
         }
     }
 
+    /// Returns whether a polymorphism was found.
+    pub fn found(&self) -> bool {
+        self.polymorphism.is_some() || !self.polymorphisms.is_empty()
+    }
+
+    /// Returns the average number of allocations per search step.
+    pub fn allocations_per_step(&self) -> f64 {
+        self.allocations as f64 / self.steps.max(1) as f64
+    }
+
     pub fn format(&self) -> String {
         let total_time = self.indicator_time + self.ac_time + self.search_time;
         format!(
-            "{},{},{:?},{:?},{:?},{:?}",
-            if self.polymorphism.is_some() {
+            "{},{},{:?},{:?},{:?},{:?},{},{},{},{},{},{},{}",
+            if self.found() {
                 'y'
             } else {
                 'n'
@@ -84,16 +142,30 @@ impl Metrics {
             self.indicator_time,
             self.ac_time,
             self.search_time,
-            total_time
+            total_time,
+            self.steps,
+            self.allocations,
+            self.consistency,
+            self.jumps,
+            self.nogoods,
+            self.restarts,
+            self.solutions.map_or(String::new(), |n| n.to_string())
         )
     }
 
     pub fn print_console(
         &self,
         config: &PolymorphismConfiguration,
-        triad: &Triad,
+        triad: &dyn Display,
     ) -> Result<(), Error> {
-        if self.polymorphism.is_some() {
+        if let Some(n) = self.solutions {
+            let msg = format!(" {} has {} {} polymorphism(s)!\n", triad, n, config);
+            if n > 0 {
+                println!("{}", format!("\t\u{2714}{}", msg).green());
+            } else {
+                println!("{}", format!("\t\u{2718}{}", msg).red());
+            }
+        } else if self.found() {
             let msg = format!(
                 "\t\u{2714} {} does have a(n) {} polymorphism!\n",
                 triad, config
@@ -107,7 +179,15 @@ impl Metrics {
             println!("{}", msg.red());
         };
         let total_time = self.indicator_time + self.ac_time + self.search_time;
+        println!("consistency: {}", self.consistency);
         println!("backtracked: {}", self.backtracked);
+        println!("jumps: {} (nogoods: {})", self.jumps, self.nogoods);
+        println!("restarts: {}", self.restarts);
+        println!(
+            "steps: {} ({:.2} allocations/step)",
+            self.steps,
+            self.allocations_per_step()
+        );
         println!("indicator_time: {:?}", self.indicator_time);
         println!("ac_time: {:?}", self.ac_time);
         println!("search_time: {:?}", self.search_time);
@@ -116,3 +196,40 @@ impl Metrics {
         Ok(())
     }
 }
+
+thread_local! {
+    static ALLOCATIONS: Cell<u64> = const { Cell::new(0) };
+}
+
+/// A global allocator that counts the allocations of each thread on top of the
+/// system allocator. The binary installs it with `#[global_allocator]`, such
+/// that the search can report its allocations in `Metrics`. Since the counts
+/// are per thread, parallel searches measure each worker on its own thread and
+/// add the counts up.
+pub struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+/// Returns the number of allocations of the current thread so far.
+pub fn allocations() -> u64 {
+    ALLOCATIONS.try_with(|n| n.get()).unwrap_or(0)
+}
@@ -1,20 +1,30 @@
 //! A homomorphism from H^k to H.
 use std::{
-    collections::HashMap,
+    collections::{hash_map::Entry, HashMap},
     convert::TryFrom,
     fmt::{self, Debug, Display},
     hash::Hash,
+    ops::RangeInclusive,
+    str::FromStr,
     time::Instant,
 };
 
 use crate::{
-    adjacency_list::AdjacencyList,
-    consistency::{List, Lists},
+    adjacency_list::{AdjacencyList, DenseID},
+    consistency::{backtrack_search_lists, List, Lists},
+    dense_graph::DenseGraph,
+    graph::{Graph, MutableGraph},
+    identity::{Class, Identities},
     metrics::Metrics,
+    power_graph::PowerGraph,
+};
+use crate::{
+    consistency::Consistency,
+    list,
+    search::{automorphisms, Backtracking, Parallelism, Restarts, Search, VariableOrder},
 };
-use crate::{consistency::backtrack_search_lists, list};
 
-use super::triad::{level, Triad};
+use super::triad::Triad;
 
 type Identity = fn(arity: &Arity, num: u32) -> Vec<Vec<Vec<u32>>>;
 
@@ -32,6 +42,11 @@ pub fn wnu(arity: &Arity, num: u32) -> Vec<Vec<Vec<u32>>> {
                 v.append(&mut wnu_i(*k, i, num));
                 v.append(&mut wnu_i(*l, i, num));
             }
+            Arity::Multiple(arities) => {
+                for &k in arities {
+                    v.append(&mut wnu_i(k, i, num));
+                }
+            }
         }
         vec.push(v);
     }
@@ -86,6 +101,75 @@ pub fn siggers(_: &Arity, num: u32) -> Vec<Vec<Vec<u32>>> {
     vec
 }
 
+/// Returns a set of sets of vertices that should be contracted when searching
+/// for cyclic identity of arity `arity` of a graph with `num` nodes, i.e. the
+/// orbits of the cyclic shift of the tuples.
+pub fn cyclic(arity: &Arity, num: u32) -> Vec<Vec<Vec<u32>>> {
+    let mut vec = Vec::<Vec<Vec<u32>>>::new();
+    for k in arity.arities() {
+        let mut tuple = vec![0; k as usize];
+        loop {
+            let mut orbit = vec![tuple.clone()];
+            let mut shifted = tuple.clone();
+            shifted.rotate_left(1);
+            // Each orbit is added once, for its lexicographically least tuple
+            let mut least = true;
+            while shifted != tuple {
+                least &= shifted > tuple;
+                orbit.push(shifted.clone());
+                shifted.rotate_left(1);
+            }
+            if least && orbit.len() > 1 {
+                vec.push(orbit);
+            }
+            match tuple.iter().rposition(|&a| a + 1 < num) {
+                Some(i) => {
+                    tuple[i] += 1;
+                    tuple[i + 1..].iter_mut().for_each(|a| *a = 0);
+                }
+                None => break,
+            }
+        }
+    }
+    vec
+}
+
+/// Returns a set of sets of vertices that should be contracted when searching
+/// for totally symmetric identity of arity `arity` of a graph with `num` nodes,
+/// i.e. the tuples with the same set of entries.
+pub fn totally_symmetric(arity: &Arity, num: u32) -> Vec<Vec<Vec<u32>>> {
+    let mut vec = Vec::<Vec<Vec<u32>>>::new();
+    for k in arity.arities() {
+        let mut classes = HashMap::<Vec<u32>, usize>::new();
+        let mut tuple = vec![0; k as usize];
+        loop {
+            let mut set = tuple.clone();
+            set.sort_unstable();
+            set.dedup();
+            let c = *classes.entry(set).or_insert_with(|| {
+                vec.push(Vec::new());
+                vec.len() - 1
+            });
+            vec[c].push(tuple.clone());
+            match tuple.iter().rposition(|&a| a + 1 < num) {
+                Some(i) => {
+                    tuple[i] += 1;
+                    tuple[i + 1..].iter_mut().for_each(|a| *a = 0);
+                }
+                None => break,
+            }
+        }
+    }
+    vec.retain(|class| class.len() > 1);
+    vec
+}
+
+/// Returns the primes in `range`, e.g. the arities of the cyclic polymorphisms
+/// that witness a Taylor polymorphism.
+pub fn primes(range: RangeInclusive<u32>) -> impl Iterator<Item = u32> {
+    range.filter(|&p| p >= 2 && (2..).take_while(|d| d * d <= p).all(|d| p % d != 0))
+}
+
 /// f(x,...,x,y) = f(x,...,x,y,x) = ... = f(y,x,...,x)
 pub fn wnu_p<T: Eq + Clone + Hash + Debug>(a: &[T], b: &[T]) -> bool {
     assert!(a.len() >= 2 && b.len() >= 2, "length must be at least 2!");
@@ -108,7 +192,7 @@ fn wnu_elem<T: Eq + Clone + Hash + Debug>(x: &[T]) -> WNU<T> {
 
     let len = elem_freq.len();
     if len == 1 {
-        return WNU::Even(elem_freq.keys().cloned().next().unwrap());
+        return WNU::Even(elem_freq.keys().next().cloned().unwrap());
     }
     if len == 2 {
         let vec = elem_freq.into_iter().collect::<Vec<_>>();
@@ -147,7 +231,7 @@ fn majority_p<T: Eq + Clone>(a: &[T], b: &[T]) -> bool {
     assert!(a.len() == 3 && b.len() == 3, "length must be equal to 3!");
     let v = major_elem(a);
     let w = major_elem(b);
-    v.clone().and(w).map_or(false, |x| x == v.unwrap())
+    v.clone().and(w).is_some_and(|x| x == v.unwrap())
 }
 
 /// Returns an element if it occurs more often than all others, None otherwise.
@@ -178,17 +262,29 @@ where
     map: HashMap<Vec<T>, T>,
 }
 
-impl<V0: Clone + Eq + Hash + Debug> TryFrom<Lists<Vec<V0>, V0>> for Polymorphism<V0> {
-    type Error = &'static str;
+impl<T: Clone + Eq + Hash> Polymorphism<T> {
+    /// Returns the value of the tuple `tuple`.
+    pub fn get(&self, tuple: &[T]) -> Option<&T> {
+        self.map.get(tuple)
+    }
+}
+
+impl<V0: DenseID + Debug> TryFrom<Lists<Vec<V0>, V0>> for Polymorphism<V0> {
+    type Error = String;
 
     fn try_from(lists: Lists<Vec<V0>, V0>) -> Result<Self, Self::Error> {
         let mut map = HashMap::<Vec<V0>, V0>::new();
         for (k, v) in lists.iter() {
             if v.size() == 1 {
-                map.insert(k.clone(), v.iter().next().unwrap().clone());
+                map.insert(k.clone(), v.iter().next().unwrap());
             } else {
-                println!("{:?}, {:?}", k, v);
-                return Err("Unable to construct polymorphism from the given lists");
+                return Err(format!(
+                    "Unable to construct polymorphism from the given lists, \
+                     tuple {:?} has {} values {:?}",
+                    k,
+                    v.size(),
+                    v.iter().collect::<Vec<_>>()
+                ));
             }
         }
         Ok(Polymorphism { map })
@@ -220,21 +316,37 @@ where
 /// # Example
 ///
 /// ```no_run
-/// let graph: AdjacencyList<u32> = Triad::from("10,10,0").into();
+/// use tripolys::adjacency_list::AdjacencyList;
+/// use tripolys::polymorphism::{commutative, Arity, PolymorphismSearcher};
+/// use tripolys::triad::Triad;
+///
+/// let triad: Triad = "10,10,0".parse().unwrap();
 /// let res = PolymorphismSearcher::new(Arity::Single(2))
 ///     .identity(commutative)
 ///     .conservative(true)
-///     .search(graph, &ac_3_lists);
-/// assert!(res.is_some());
+///     .search(&AdjacencyList::<u32>::from(&triad));
+/// assert!(res.polymorphism.is_some());
 /// ```
 /// [`PolymorphismSearcher::search`]: ./struct.PolymorphismSearcher.html#method.search
 #[allow(missing_debug_implementations)]
 pub struct PolymorphismSearcher {
     arity: Arity,
     identity: Option<Identity>,
+    identities: Option<Identities>,
     conservative: bool,
     idempotent: bool,
     majority: bool,
+    implicit: bool,
+    consistency: Consistency,
+    backtracking: Backtracking,
+    restarts: Restarts,
+    seed: Option<u64>,
+    parallelism: Parallelism,
+    variable_order: VariableOrder,
+    value_order: ValueOrder,
+    count: bool,
+    limit: Option<u64>,
+    modulo_automorphisms: bool,
     optimization: Option<Optimization>,
 }
 
@@ -245,9 +357,21 @@ impl PolymorphismSearcher {
         PolymorphismSearcher {
             arity,
             identity: None,
+            identities: None,
             conservative: false,
             idempotent: false,
             majority: false,
+            implicit: false,
+            consistency: Consistency::default(),
+            backtracking: Backtracking::Chronological,
+            restarts: Restarts::None,
+            seed: None,
+            parallelism: Parallelism::None,
+            variable_order: VariableOrder::Static,
+            value_order: ValueOrder::Index,
+            count: false,
+            limit: None,
+            modulo_automorphisms: false,
             optimization: None,
         }
     }
@@ -255,6 +379,18 @@ impl PolymorphismSearcher {
     /// The identity the polymorphism should satisfy.
     pub fn identity(mut self, indentity: Identity) -> Self {
         self.identity = Some(indentity);
+        self.identities = None;
+        self
+    }
+
+    /// The system of height 1 identities the polymorphism should satisfy, see
+    /// [`Identities`]. It replaces an identity and its arity must be the arity
+    /// of the searcher.
+    ///
+    /// [`Identities`]: ../identity/struct.Identities.html
+    pub fn identities(mut self, identities: Identities) -> Self {
+        self.identities = Some(identities);
+        self.identity = None;
         self
     }
 
@@ -270,7 +406,9 @@ impl PolymorphismSearcher {
         self
     }
 
-    /// Whether the polymorphism should be a majority operation.
+    /// Whether the polymorphism should be a majority operation, or a
+    /// near-unanimity operation if its arity is greater than 3. This pins the
+    /// classes of the `wnu` identity to their unanimous value.
     pub const fn majority(mut self, m: bool) -> Self {
         self.majority = m;
         self
@@ -281,61 +419,476 @@ impl PolymorphismSearcher {
         self
     }
 
+    /// Whether the indicator graph should be an implicit view of the power
+    /// graph, see [`PowerGraph`], instead of being materialised.
+    ///
+    /// [`PowerGraph`]: ../power_graph/struct.PowerGraph.html
+    pub const fn implicit(mut self, i: bool) -> Self {
+        self.implicit = i;
+        self
+    }
+
+    /// The local consistencies that are established before and maintained
+    /// during the search, see [`Consistency`].
+    ///
+    /// [`Consistency`]: ../consistency/struct.Consistency.html
+    pub const fn consistency(mut self, c: Consistency) -> Self {
+        self.consistency = c;
+        self
+    }
+
+    /// How the search backtracks from assignments that can't be extended, see
+    /// [`Backtracking`].
+    ///
+    /// [`Backtracking`]: ../search/enum.Backtracking.html
+    pub const fn backtracking(mut self, backtracking: Backtracking) -> Self {
+        self.backtracking = backtracking;
+        self
+    }
+
+    /// When the search restarts, see [`Restarts`].
+    ///
+    /// [`Restarts`]: ../search/enum.Restarts.html
+    pub const fn restarts(mut self, restarts: Restarts) -> Self {
+        self.restarts = restarts;
+        self
+    }
+
+    /// The seed of the random tie-breaking of the search. Without a seed the
+    /// search is deterministic.
+    pub const fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Whether the search runs in parallel on rayon's thread pool, see
+    /// [`Parallelism`]. The searches of a portfolio use the configured
+    /// variable order and seed in turn with other orders and seeds.
+    ///
+    /// [`Parallelism`]: ../search/enum.Parallelism.html
+    pub const fn parallelism(mut self, parallelism: Parallelism) -> Self {
+        self.parallelism = parallelism;
+        self
+    }
+
+    /// The order in which the vertices of the indicator graph are assigned,
+    /// see [`VariableOrder`].
+    ///
+    /// [`VariableOrder`]: ../search/enum.VariableOrder.html
+    pub const fn variable_order(mut self, order: VariableOrder) -> Self {
+        self.variable_order = order;
+        self
+    }
+
+    /// The value that is tried first for a tuple, see [`ValueOrder`].
+    ///
+    /// [`ValueOrder`]: ./enum.ValueOrder.html
+    pub const fn value_order(mut self, order: ValueOrder) -> Self {
+        self.value_order = order;
+        self
+    }
+
+    /// Whether the search counts the polymorphisms instead of finding one. The
+    /// count is recorded in `Metrics::solutions`, and the search runs
+    /// sequentially, without restarts and without an optimization.
+    pub const fn count(mut self, c: bool) -> Self {
+        self.count = c;
+        self
+    }
+
+    /// The number of polymorphisms after which counting stops.
+    pub const fn limit(mut self, limit: u64) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Whether only one polymorphism f is counted for all σ ∘ f, where σ is an
+    /// automorphism of the graph that preserves the lists of the search, e.g.
+    /// the conservative lists.
+    pub const fn modulo_automorphisms(mut self, m: bool) -> Self {
+        self.modulo_automorphisms = m;
+        self
+    }
+
     /// Searches for the configured polymorphism of graph `g` by using algorithm
     /// `algorithm` as a heuristic. Returns all the relevant metrics recorded
     /// during the search.
-    pub fn search(&self, g: &AdjacencyList<u32>) -> Metrics {
+    ///
+    /// The indicator graph is built with the same graph representation as `g`,
+    /// unless the searcher is configured to use an implicit indicator graph.
+    pub fn search<G>(&self, g: &G) -> Metrics
+    where
+        G: MutableGraph<Vertex = u32>,
+    {
+        // The powers of several symbols may have the same arity, so that only
+        // the blocks of an implicit indicator graph tell them apart
+        if self.implicit || matches!(self.arity, Arity::Multiple(_)) {
+            return self.search_implicit(g);
+        }
         let mut metrics = Metrics::new();
         let indicator_start = Instant::now();
         let mut indicator = match self.arity {
             Arity::Single(k) => g.power(k),
             Arity::Dual(k, l) => g.power(k).union(&g.power(l)),
+            Arity::Multiple(_) => unreachable!(),
         };
 
         let mut lists = Lists::<Vec<u32>, u32>::new();
-        if let Some(p) = self.identity {
-            let vecs = p(&self.arity, g.vertices().count() as u32);
-            for vec in vecs {
-                for i in 1..vec.len() {
-                    indicator.contract_vertices(&vec[0], &vec[i]);
-                }
-                if self.majority {
-                    lists.insert(vec[0].clone(), list![vec[0][0]]);
-                }
+        // The tuples of each contracted vertex
+        let mut members = HashMap::<Vec<u32>, Vec<Vec<u32>>>::new();
+        for class in self.classes(g.vertices().count() as u32) {
+            let u = &class.tuples[0].1;
+            for (_, v) in &class.tuples[1..] {
+                indicator.contract_vertices(u, v);
             }
+            if let Some(list) = pinned(&class) {
+                lists.insert(u.clone(), list);
+            }
+            let tuples = class.tuples.into_iter().map(|(_, tuple)| tuple).collect::<Vec<_>>();
+            members.insert(tuples[0].clone(), tuples);
         }
 
-        if self.optimization.is_some() {
+        // The optimization needs levels, so it's skipped for unbalanced graphs.
+        // It drops the polymorphisms on the other components, so it's also
+        // skipped when counting.
+        let optimization = self.optimization.as_ref().filter(|_| !self.count);
+        if let Some(levels) = optimization.and_then(|_| levels(g)) {
             // Only consider consider the component with vertices (u, v) where u and
             // v are on the same level.
-            let mut graph = AdjacencyList::<Vec<u32>>::new();
-            let triad = Triad::try_from(g.clone()).unwrap();
+            let mut graph = G::Power::default();
             for comp in indicator.components() {
                 let v = comp.vertices().next().unwrap();
-                if level(v[0], &triad) == level(v[1], &triad) {
+                if levels[&v[0]] == levels[&v[1]] {
                     graph = graph.union(&comp);
                 }
             }
             indicator = graph;
         }
 
-        if self.conservative {
-            for vec in indicator.vertices() {
-                lists.insert(vec.clone(), vec.iter().copied().collect::<List<_>>());
+        for vec in indicator.vertices() {
+            let tuples = members.get(vec).cloned().unwrap_or_else(|| vec![vec.clone()]);
+            if let Some(list) = self.restrict(lists.get(vec).cloned(), tuples) {
+                lists.insert(vec.clone(), list);
             }
         }
 
-        if self.idempotent {
-            for vec in indicator.vertices() {
-                if is_all_same(vec) {
-                    lists.insert(vec.clone(), list![vec[0]]);
+        metrics.indicator_time = indicator_start.elapsed();
+
+        let tuple = |v: &Vec<u32>| v.clone();
+        if let Some(lists) = self.backtrack(&indicator, g, lists, tuple, &mut metrics) {
+            metrics.polymorphism = Some(Polymorphism::try_from(lists).unwrap());
+        }
+
+        metrics
+    }
+}
+
+impl PolymorphismSearcher {
+    // Returns the classes of tuples that are contracted for the identity or
+    // identities on a graph with `num` nodes.
+    fn classes(&self, num: u32) -> Vec<Class> {
+        let p = match (&self.identities, self.identity) {
+            (Some(identities), _) => return identities.contractions(num),
+            (None, Some(p)) => p,
+            (None, None) => return Vec::new(),
+        };
+        // The tuples of an identity are told apart by their arities
+        let arities = self.arity.arities();
+        let block = |tuple: &Vec<u32>| arities.iter().position(|&k| k as usize == tuple.len());
+        p(&self.arity, num)
+            .into_iter()
+            .map(|vecs| Class {
+                values: if self.majority { vec![vecs[0][0]] } else { Vec::new() },
+                tuples: vecs.into_iter().map(|v| (block(&v).unwrap(), v)).collect(),
+            })
+            .collect()
+    }
+}
+
+impl PolymorphismSearcher {
+    // Restricts `list`, the list of a vertex of the indicator graph, if the
+    // polymorphism should be conservative or idempotent on one of its
+    // `tuples`. Returns None, if the list isn't restricted at all.
+    fn restrict(
+        &self,
+        mut list: Option<List<u32>>,
+        tuples: impl IntoIterator<Item = Vec<u32>>,
+    ) -> Option<List<u32>> {
+        for tuple in tuples {
+            if self.conservative {
+                list = Some(intersect(list, tuple.iter().copied().collect()));
+            }
+            if self.idempotent && is_all_same(&tuple) {
+                list = Some(intersect(list, list![tuple[0]]));
+            }
+        }
+        list
+    }
+}
+
+fn intersect(list: Option<List<u32>>, other: List<u32>) -> List<u32> {
+    match list {
+        Some(list) => list.intersection(&other),
+        None => other,
+    }
+}
+
+// Returns the list a class is restricted to by its pinned values, which is
+// empty if they disagree.
+fn pinned(class: &Class) -> Option<List<u32>> {
+    match class.values[..] {
+        [] => None,
+        [a] => Some(list![a]),
+        _ => Some(List::new()),
+    }
+}
+
+/// Returns the level of each vertex of the graph `g`, i.e. the number of forward
+/// edges minus the number of backward edges on a path from the first vertex of
+/// its component. Returns None, if `g` is not balanced.
+fn levels<G: Graph<Vertex = u32>>(g: &G) -> Option<HashMap<u32, i32>> {
+    let mut levels = HashMap::<u32, i32>::new();
+    for &v in g.vertices() {
+        if levels.contains_key(&v) {
+            continue;
+        }
+        levels.insert(v, 0);
+        let mut stack = vec![v];
+        while let Some(u) = stack.pop() {
+            let level = levels[&u];
+            let neighbors = g
+                .out_neighbors(&u)
+                .map(|w| (w, level + 1))
+                .chain(g.in_neighbors(&u).map(|w| (w, level - 1)))
+                .collect::<Vec<_>>();
+            for (w, l) in neighbors {
+                match levels.entry(w) {
+                    Entry::Vacant(e) => {
+                        e.insert(l);
+                        stack.push(w);
+                    }
+                    Entry::Occupied(e) if *e.get() != l => return None,
+                    Entry::Occupied(_) => {}
+                }
+            }
+        }
+    }
+    Some(levels)
+}
+
+/// The maximum number of pairs (S, T) of sets of vertices that `width_1`
+/// checks for an edge of U(H). These are the pairs with T ⊆ N⁺(S), at most
+/// 2^(|L| + |L'|) for each pair of adjacent levels L and L' of H, or 4^|H| if H
+/// is unbalanced.
+pub const MAX_WIDTH_1_PAIRS: u64 = 1 << 24;
+
+/// Decides whether arc consistency solves CSP(H) for the graph `h`, i.e.
+/// whether H has totally symmetric polymorphisms of all arities. This is the
+/// case iff there is a homomorphism from U(H) to H. The vertices of U(H) are
+/// the non-empty sets of vertices of H, and (S, T) is an edge iff every vertex
+/// of S has an out-neighbor in T and every vertex of T has an in-neighbor in S.
+///
+/// If H is balanced, U(H) only contains the sets within a level, since a set
+/// with vertices on several levels can be mapped like its part on the lowest
+/// one. Otherwise U(H) has 2^|H| - 1 vertices. Returns an error, if building
+/// U(H) checks more than [`MAX_WIDTH_1_PAIRS`] pairs of sets.
+///
+/// The homomorphism is returned as `polymorphism` of the metrics, with the
+/// sets as sorted tuples.
+///
+/// [`MAX_WIDTH_1_PAIRS`]: ./constant.MAX_WIDTH_1_PAIRS.html
+///
+/// # Example
+///
+/// ```
+/// use tripolys::adjacency_list::AdjacencyList;
+/// use tripolys::polymorphism::width_1;
+///
+/// // AC solves the CSP of every oriented path
+/// let mut path = AdjacencyList::<u32>::new();
+/// for v in 0..4 {
+///     path.add_vertex(v);
+/// }
+/// path.add_edge(&0, &1);
+/// path.add_edge(&2, &1);
+/// path.add_edge(&2, &3);
+/// assert!(width_1(&path).unwrap().polymorphism.is_some());
+/// ```
+pub fn width_1<G: Graph<Vertex = u32>>(h: &G) -> Result<Metrics, &'static str> {
+    const TOO_LARGE: &str = "U(H) is too large, it has too many pairs of sets to check";
+    let mut metrics = Metrics::new();
+    let indicator_start = Instant::now();
+
+    // The sets of vertices whose subsets are the vertices of U(H)
+    let groups = match levels(h) {
+        Some(levels) => {
+            let mut groups = HashMap::<i32, Vec<u32>>::new();
+            for (&v, &l) in &levels {
+                groups.entry(l).or_default().push(v);
+            }
+            let mut groups = groups.into_iter().collect::<Vec<_>>();
+            groups.sort_unstable();
+            groups.into_iter().map(|(_, group)| group).collect()
+        }
+        None => vec![h.vertices().copied().collect::<Vec<_>>()],
+    };
+    // Every set is paired at least with the empty set, so the number of sets
+    // is bounded as well.
+    let mut sets = 0u64;
+    for group in &groups {
+        sets += 1 << group.len().min(63);
+        if sets > MAX_WIDTH_1_PAIRS {
+            return Err(TOO_LARGE);
+        }
+    }
+
+    // A set is a bitmask over its group, its vertex in U(H) is the offset of
+    // the group plus the bitmask minus one.
+    let mut position = HashMap::<u32, (usize, u32)>::new();
+    let mut offsets = Vec::with_capacity(groups.len());
+    let mut offset = 0;
+    for (g, group) in groups.iter().enumerate() {
+        for (i, &v) in group.iter().enumerate() {
+            position.insert(v, (g, i as u32));
+        }
+        offsets.push(offset);
+        offset += (1u32 << group.len()) - 1;
+    }
+
+    // The group of the out-neighbors of each group, and the out-neighbors of
+    // each of its sets as a bitmask over that group.
+    let mut neighbors = Vec::with_capacity(groups.len());
+    let mut pairs = 0u64;
+    for group in &groups {
+        let mut target = None;
+        let singletons = group
+            .iter()
+            .map(|v| {
+                h.out_neighbors(v).fold(0u64, |mask, w| {
+                    let (g, i) = position[&w];
+                    target = Some(g);
+                    mask | 1 << i
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut masks = vec![0u64; 1 << group.len()];
+        for s in 1..masks.len() {
+            masks[s] = masks[s & (s - 1)] | singletons[s.trailing_zeros() as usize];
+            pairs += 1 << masks[s].count_ones();
+            if pairs > MAX_WIDTH_1_PAIRS {
+                return Err(TOO_LARGE);
+            }
+        }
+        neighbors.push((target, singletons, masks));
+    }
+
+    let mut u = DenseGraph::<u32>::new();
+    for v in 0..offset {
+        u.add_vertex(v);
+    }
+    for (g, (target, singletons, masks)) in neighbors.iter().enumerate() {
+        let target = match target {
+            Some(target) => offsets[*target],
+            None => continue,
+        };
+        for (s, &n) in masks.iter().enumerate().skip(1) {
+            // Every subset T of the out-neighbors has an in-neighbor in S for
+            // each of its vertices, it remains to check that T covers S.
+            let mut t = n & n.wrapping_neg();
+            while t != 0 {
+                if bits(s as u64).all(|i| singletons[i] & t != 0) {
+                    u.add_edge(&(offsets[g] + s as u32 - 1), &(target + t as u32 - 1));
                 }
+                t = t.wrapping_sub(n) & n;
+            }
+        }
+    }
+    metrics.indicator_time = indicator_start.elapsed();
+
+    if let Some(lists) = backtrack_search_lists(&u, h, Lists::new(), &mut metrics) {
+        // Translate the vertices of U(H) back into sets
+        let mut sets = Lists::<Vec<u32>, u32>::new();
+        for (v, list) in lists {
+            let g = offsets.partition_point(|&o| o <= v) - 1;
+            let mask = (v - offsets[g] + 1) as u64;
+            let mut set = bits(mask).map(|i| groups[g][i]).collect::<Vec<_>>();
+            set.sort_unstable();
+            sets.insert(set, list);
+        }
+        metrics.polymorphism = Some(Polymorphism::try_from(sets).unwrap());
+    }
+    Ok(metrics)
+}
+
+// Returns the positions of the set bits of `mask` in increasing order.
+fn bits(mut mask: u64) -> impl Iterator<Item = usize> {
+    std::iter::from_fn(move || {
+        if mask == 0 {
+            return None;
+        }
+        let i = mask.trailing_zeros() as usize;
+        mask &= mask - 1;
+        Some(i)
+    })
+}
+
+impl PolymorphismSearcher {
+    // Same as `search`, but on a `PowerGraph` whose vertices are the codes of
+    // the tuples.
+    fn search_implicit<G: Graph<Vertex = u32>>(&self, g: &G) -> Metrics {
+        let mut metrics = Metrics::new();
+        let indicator_start = Instant::now();
+        let mut indicator = PowerGraph::new(g, &self.arity.arities());
+
+        let mut lists = Lists::<u64, u32>::new();
+        for class in self.classes(g.vertex_count() as u32) {
+            let (b, ref tuple) = class.tuples[0];
+            let u = indicator.encode_in(b, tuple);
+            for (b, v) in &class.tuples[1..] {
+                indicator.contract_vertices(&u, &indicator.encode_in(*b, v));
+            }
+            if let Some(list) = pinned(&class) {
+                let r = indicator.representative(u).unwrap();
+                lists.insert(r, list);
+            }
+        }
+
+        let optimization = self.optimization.as_ref().filter(|_| !self.count);
+        if let Some(levels) = optimization.and_then(|_| levels(g)) {
+            // Only consider the vertices (u, v) where u and v are on the same
+            // level, these are exactly the components the materialised search
+            // keeps.
+            indicator.retain(|v| levels[&v[0]] == levels[&v[1]]);
+        }
+
+        for &code in indicator.vertices() {
+            let tuples = indicator.members(code).iter().map(|&c| indicator.tuple(c));
+            if let Some(list) = self.restrict(lists.get(&code).cloned(), tuples) {
+                lists.insert(code, list);
             }
         }
 
         metrics.indicator_time = indicator_start.elapsed();
 
-        if let Some(lists) = backtrack_search_lists(&indicator, g, lists, &mut metrics) {
+        let tuple = |&code: &u64| indicator.tuple(code);
+        let res = self.backtrack(&indicator, g, lists, tuple, &mut metrics);
+        if let (Some(lists), Arity::Multiple(arities)) = (&res, &self.arity) {
+            // One table for each symbol with all tuples of its block
+            let mut tables = vec![Lists::<Vec<u32>, u32>::new(); arities.len()];
+            for (&r, list) in lists.iter() {
+                for &code in indicator.members(r) {
+                    tables[indicator.block(code)].insert(indicator.tuple(code), list.clone());
+                }
+            }
+            metrics.polymorphisms = tables
+                .into_iter()
+                .map(|table| Polymorphism::try_from(table).unwrap())
+                .collect();
+        } else if let Some(lists) = res {
+            let lists = lists
+                .into_iter()
+                .map(|(code, list)| (indicator.tuple(code), list))
+                .collect::<Lists<_, _>>();
             metrics.polymorphism = Some(Polymorphism::try_from(lists).unwrap());
         }
 
@@ -343,6 +896,53 @@ impl PolymorphismSearcher {
     }
 }
 
+impl PolymorphismSearcher {
+    // Searches for a homomorphism from the indicator graph to `g` with the
+    // configured consistencies and orders. The tuple of a vertex of the
+    // indicator graph is given by `tuple`.
+    fn backtrack<I, G>(
+        &self,
+        indicator: &I,
+        g: &G,
+        lists: Lists<I::Vertex, u32>,
+        tuple: impl Fn(&I::Vertex) -> Vec<u32>,
+        metrics: &mut Metrics,
+    ) -> Option<Lists<I::Vertex, u32>>
+    where
+        I: Graph,
+        I::Vertex: Debug + Send + Sync,
+        G: Graph<Vertex = u32>,
+    {
+        metrics.consistency = self.consistency;
+        if self.count {
+            metrics.solutions = Some(0);
+        }
+        let ac_start = Instant::now();
+        let res = self.consistency.preprocessing.apply(indicator, g, lists);
+        metrics.ac_time = ac_start.elapsed();
+        let lists = res?;
+
+        let value_order = self.value_order;
+        let mut search = Search::new(indicator, g, &lists)
+            .propagation(self.consistency.propagation)
+            .backtracking(self.backtracking)
+            .restarts(self.restarts)
+            .variable_order(self.variable_order)
+            .prefer(|v| value_order.preferred(&tuple(v)));
+        if let Some(seed) = self.seed {
+            search = search.seed(seed);
+        }
+        if self.count {
+            if self.modulo_automorphisms {
+                search = search.modulo(&automorphisms(g));
+            }
+            metrics.solutions = Some(search.count(self.limit, metrics));
+            return None;
+        }
+        search.run_with(self.parallelism, metrics)
+    }
+}
+
 fn is_all_same<T: PartialEq>(arr: &[T]) -> bool {
     arr.windows(2).all(|w| w[0] == w[1])
 }
@@ -354,6 +954,29 @@ pub enum Arity {
     Single(u32),
     /// Needed for e.g. 3-4 weak near unamity polymorphisms.
     Dual(u32, u32),
+    /// The arities of several operation symbols, e.g. of Jónsson terms. Each
+    /// symbol has its own power in the indicator graph, even if the arities
+    /// are the same.
+    Multiple(Vec<u32>),
+}
+
+impl Arity {
+    /// Returns the arities of the powers of the indicator graph.
+    pub fn arities(&self) -> Vec<u32> {
+        match self {
+            Arity::Single(k) => vec![*k],
+            Arity::Dual(k, l) => vec![*k, *l],
+            Arity::Multiple(arities) => arities.clone(),
+        }
+    }
+
+    // Returns the arity of a searcher for the polymorphisms of `identities`.
+    fn of(identities: &Identities) -> Arity {
+        match identities.arities()[..] {
+            [k] => Arity::Single(k),
+            _ => Arity::Multiple(identities.arities()),
+        }
+    }
 }
 
 /// The registered polymorphisms.
@@ -369,6 +992,18 @@ pub enum PolymorphismKind {
     WNU34,
     /// 3 weak near unamity polymorphism
     WNU3,
+    /// near unamity polymorphism of the given arity
+    NU(u32),
+    /// weak near unamity polymorphism of the given arity
+    WNU(u32),
+    /// weak near unamity polymorphisms of the two given arities
+    WNUPair(u32, u32),
+    /// cyclic polymorphism of the given arity
+    Cyclic(u32),
+    /// totally symmetric polymorphism of the given arity
+    TotallySymmetric(u32),
+    /// polymorphism that satisfies a system of height 1 identities
+    Identities(Identities),
 }
 
 pub struct PolymorphismConfiguration {
@@ -400,6 +1035,57 @@ impl Display for PolymorphismConfiguration {
     }
 }
 
+/// The value a polymorphism search tries first for a tuple, the other values
+/// are tried in order of increasing index.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValueOrder {
+    /// No value is preferred
+    Index,
+    /// The most frequent entry of the tuple, which is the value of an
+    /// idempotent or near-unanimity polymorphism wherever it is determined
+    Idempotent,
+    /// The first entry of the tuple, i.e. the value of the first projection
+    Projection,
+}
+
+impl ValueOrder {
+    /// Returns the value that is tried first for `tuple`.
+    pub fn preferred(&self, tuple: &[u32]) -> Option<u32> {
+        match self {
+            ValueOrder::Index => None,
+            ValueOrder::Idempotent => {
+                let count = |a: &u32| tuple.iter().filter(|&b| a == b).count();
+                // the first of the most frequent entries
+                tuple.iter().rev().max_by_key(|a| count(a)).copied()
+            }
+            ValueOrder::Projection => tuple.first().copied(),
+        }
+    }
+}
+
+impl Display for ValueOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValueOrder::Index => write!(f, "index"),
+            ValueOrder::Idempotent => write!(f, "idempotent"),
+            ValueOrder::Projection => write!(f, "projection"),
+        }
+    }
+}
+
+impl FromStr for ValueOrder {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "index" => Ok(ValueOrder::Index),
+            "idempotent" => Ok(ValueOrder::Idempotent),
+            "projection" => Ok(ValueOrder::Projection),
+            _ => Err("Unknown value order"),
+        }
+    }
+}
+
 /// Possible optimizations for the polymophism search
 #[derive(Debug)]
 pub enum Optimization {
@@ -414,6 +1100,12 @@ impl fmt::Display for PolymorphismKind {
             PolymorphismKind::Siggers => write!(f, "siggers"),
             PolymorphismKind::WNU34 => write!(f, "3/4wnu"),
             PolymorphismKind::WNU3 => write!(f, "3wnu"),
+            PolymorphismKind::NU(k) => write!(f, "nu:{}", k),
+            PolymorphismKind::WNU(k) => write!(f, "wnu:{}", k),
+            PolymorphismKind::WNUPair(k, l) => write!(f, "wnu:{},{}", k, l),
+            PolymorphismKind::Cyclic(p) => write!(f, "cyclic:{}", p),
+            PolymorphismKind::TotallySymmetric(k) => write!(f, "totally-symmetric:{}", k),
+            PolymorphismKind::Identities(ref identities) => write!(f, "{}", identities),
         }
     }
 }
@@ -437,6 +1129,28 @@ impl PolymorphismSearcher {
             PolymorphismKind::WNU34 => PolymorphismSearcher::new(Arity::Dual(3, 4)).identity(wnu),
 
             PolymorphismKind::WNU3 => PolymorphismSearcher::new(Arity::Single(3)).identity(wnu),
+
+            PolymorphismKind::NU(k) => PolymorphismSearcher::new(Arity::Single(k))
+                .identity(wnu)
+                .majority(true),
+
+            PolymorphismKind::WNU(k) => PolymorphismSearcher::new(Arity::Single(k)).identity(wnu),
+
+            PolymorphismKind::WNUPair(k, l) => {
+                PolymorphismSearcher::new(Arity::Dual(k, l)).identity(wnu)
+            }
+
+            PolymorphismKind::Cyclic(p) => {
+                PolymorphismSearcher::new(Arity::Single(p)).identity(cyclic)
+            }
+
+            PolymorphismKind::TotallySymmetric(k) => {
+                PolymorphismSearcher::new(Arity::Single(k)).identity(totally_symmetric)
+            }
+
+            PolymorphismKind::Identities(ref identities) => {
+                PolymorphismSearcher::new(Arity::of(identities)).identities(identities.clone())
+            }
         };
         if config.conservative {
             searcher = searcher.conservative(true);
@@ -452,29 +1166,5 @@ impl PolymorphismSearcher {
 /// Returns None, if `list` does not have a polymorphism of kind `kind`,
 /// otherwise a polymorphism of `list` is returned.
 pub fn polymorphism_searcher(triad: &Triad, config: &PolymorphismConfiguration) -> Metrics {
-    let mut polymorphism_searcher = match config.kind {
-        PolymorphismKind::Commutative => {
-            { PolymorphismSearcher::new(Arity::Single(2)).identity(commutative) }
-                .optimize(Optimization::Commutative)
-        }
-
-        PolymorphismKind::Majority => PolymorphismSearcher::new(Arity::Single(3))
-            .identity(wnu)
-            .majority(true),
-
-        PolymorphismKind::Siggers => PolymorphismSearcher::new(Arity::Single(4)).identity(siggers),
-
-        PolymorphismKind::WNU34 => PolymorphismSearcher::new(Arity::Dual(3, 4)).identity(wnu),
-
-        PolymorphismKind::WNU3 => PolymorphismSearcher::new(Arity::Single(3)).identity(wnu),
-    };
-    if config.conservative {
-        polymorphism_searcher = polymorphism_searcher.conservative(true);
-    }
-
-    if config.idempotent {
-        polymorphism_searcher = polymorphism_searcher.idempotent(true);
-    }
-
-    polymorphism_searcher.search(&triad.into())
+    PolymorphismSearcher::get(config).search(&AdjacencyList::from(triad))
 }
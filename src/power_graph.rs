@@ -0,0 +1,296 @@
+//! An implicit view of the power graph H^k that is never materialised.
+use std::collections::HashMap;
+
+use crate::{adjacency_list::VertexID, graph::Graph};
+
+// Marks a tuple that was removed from the graph.
+const REMOVED: u64 = u64::MAX;
+
+/// The k-ary product graph of a graph H, or the disjoint union of several
+/// such powers, that computes its neighbourhoods on demand.
+///
+/// A tuple (v_1, ..., v_k) is encoded by the indices of v_1, ..., v_k in mixed
+/// radix, so a vertex of the power graph is a single `u64`. The neighbours of a
+/// tuple are derived from the adjacency rows of H. Contracting vertices only
+/// merges their classes in a union-find overlay, where the representative of
+/// a class is the vertex the others were contracted with.
+#[derive(Debug, Clone)]
+pub struct PowerGraph<V: VertexID> {
+    // Index -> Vertex of H
+    labels: Vec<V>,
+    // Vertex of H -> Index
+    indices: HashMap<V, u32>,
+    // Index -> Out-Edges of H
+    out_rows: Vec<Vec<u32>>,
+    // Index -> In-Edges of H
+    in_rows: Vec<Vec<u32>>,
+    // (arity, first code) for each power
+    blocks: Vec<(u32, u64)>,
+    // Code -> code of the representative of its class
+    root: Vec<u64>,
+    // Representative -> all codes of its class, for classes with more than one member
+    members: HashMap<u64, Vec<u64>>,
+    // Number of classes that were not removed
+    count: usize,
+}
+
+impl<V: VertexID> PowerGraph<V> {
+    /// Creates the disjoint union of the powers H^k for each k in `arities`.
+    pub fn new<G: Graph<Vertex = V>>(h: &G, arities: &[u32]) -> PowerGraph<V> {
+        let labels = h.vertices().cloned().collect::<Vec<_>>();
+        let indices = labels
+            .iter()
+            .cloned()
+            .enumerate()
+            .map(|(i, v)| (v, i as u32))
+            .collect::<HashMap<_, _>>();
+        let row = |vertices: Vec<V>| {
+            let mut row = vertices.iter().map(|w| indices[w]).collect::<Vec<_>>();
+            row.sort_unstable();
+            row
+        };
+        let out_rows = labels
+            .iter()
+            .map(|v| row(h.out_neighbors(v).collect()))
+            .collect::<Vec<_>>();
+        let in_rows = labels
+            .iter()
+            .map(|v| row(h.in_neighbors(v).collect()))
+            .collect::<Vec<_>>();
+
+        let n = labels.len() as u64;
+        let mut blocks = Vec::new();
+        let mut offset = 0;
+        for &k in arities {
+            blocks.push((k, offset));
+            offset += n.pow(k);
+        }
+
+        PowerGraph {
+            labels,
+            indices,
+            out_rows,
+            in_rows,
+            blocks,
+            root: (0..offset).collect(),
+            members: HashMap::new(),
+            count: offset as usize,
+        }
+    }
+
+    /// Returns the code of the tuple `tuple`.
+    ///
+    /// # Panics
+    ///
+    /// Panics, if there's no power of that arity or the tuple contains a vertex
+    /// that is not in H.
+    pub fn encode(&self, tuple: &[V]) -> u64 {
+        let block = self
+            .blocks
+            .iter()
+            .position(|(k, _)| *k as usize == tuple.len())
+            .expect("no power of that arity!");
+        self.encode_in(block, tuple)
+    }
+
+    /// Returns the code of the tuple `tuple` in the power with index `block`,
+    /// which tells apart powers of the same arity.
+    ///
+    /// # Panics
+    ///
+    /// Panics, if the power has another arity or the tuple contains a vertex
+    /// that is not in H.
+    pub fn encode_in(&self, block: usize, tuple: &[V]) -> u64 {
+        let (k, offset) = self.blocks[block];
+        assert!(k as usize == tuple.len(), "power has another arity!");
+        let n = self.labels.len() as u64;
+        offset
+            + tuple
+                .iter()
+                .fold(0, |code, v| code * n + u64::from(self.indices[v]))
+    }
+
+    /// Returns the tuple that is encoded by `code`.
+    pub fn tuple(&self, code: u64) -> Vec<V> {
+        self.digits(code)
+            .1
+            .into_iter()
+            .map(|d| self.labels[d as usize].clone())
+            .collect()
+    }
+
+    /// Returns the index of the power that contains `code`.
+    pub fn block(&self, code: u64) -> usize {
+        self.digits(code).0
+    }
+
+    /// Returns all codes of the class of the representative `r`.
+    pub fn members(&self, r: u64) -> &[u64] {
+        self.class(r)
+    }
+
+    /// Returns the representative of the class of `code`, or None if it was
+    /// removed.
+    pub fn representative(&self, code: u64) -> Option<u64> {
+        let r = self.root[code as usize];
+        if r == REMOVED {
+            None
+        } else {
+            Some(r)
+        }
+    }
+
+    /// Contracts the vertex `v` with the vertex `u` so that the resulting vertex
+    /// has id `u`. Contracting two vertices of the same class does nothing.
+    pub fn contract_vertices(&mut self, u: &u64, v: &u64) {
+        let ru = self.representative(*u).expect("vertex does not exist!");
+        let rv = self.representative(*v).expect("vertex does not exist!");
+        if ru == rv {
+            return;
+        }
+        let moved = self.members.remove(&rv).unwrap_or_else(|| vec![rv]);
+        for &w in &moved {
+            self.root[w as usize] = ru;
+        }
+        self.members
+            .entry(ru)
+            .or_insert_with(|| vec![ru])
+            .extend(moved);
+        self.count -= 1;
+    }
+
+    /// Removes all vertices whose representative tuple doesn't satisfy the
+    /// predicate `p`.
+    pub fn retain(&mut self, p: impl Fn(&[V]) -> bool) {
+        for code in 0..self.root.len() as u64 {
+            if self.root[code as usize] != code || p(&self.tuple(code)) {
+                continue;
+            }
+            for w in self.members.remove(&code).unwrap_or_else(|| vec![code]) {
+                self.root[w as usize] = REMOVED;
+            }
+            self.count -= 1;
+        }
+    }
+
+    // Returns the index of the block of `code` and the digits of the tuple.
+    fn digits(&self, code: u64) -> (usize, Vec<u32>) {
+        let mut digits = Vec::new();
+        let b = self.decode_into(code, &mut digits);
+        (b, digits)
+    }
+
+    // Appends the digits of the tuple of `code` to `digits` and returns the
+    // index of its block.
+    fn decode_into(&self, code: u64, digits: &mut Vec<u32>) -> usize {
+        let n = self.labels.len() as u64;
+        let b = self
+            .blocks
+            .iter()
+            .rposition(|&(_, offset)| offset <= code)
+            .unwrap();
+        let (k, offset) = self.blocks[b];
+        let start = digits.len();
+        digits.resize(start + k as usize, 0);
+        let mut x = code - offset;
+        for d in digits[start..].iter_mut().rev() {
+            *d = (x % n) as u32;
+            x /= n;
+        }
+        b
+    }
+
+    fn class(&self, r: u64) -> &[u64] {
+        match self.members.get(&r) {
+            Some(members) => members,
+            None => std::slice::from_ref(&self.root[r as usize]),
+        }
+    }
+
+    // Returns the sorted representatives of all neighbours of the class of r.
+    fn neighbors(&self, r: u64, rows: &[Vec<u32>]) -> Vec<u64> {
+        let n = self.labels.len() as u64;
+        let mut res = Vec::new();
+        for &code in self.class(r) {
+            let (b, digits) = self.digits(code);
+            let mut codes = vec![0];
+            for d in digits {
+                codes = codes
+                    .iter()
+                    .flat_map(|c| rows[d as usize].iter().map(move |&j| c * n + u64::from(j)))
+                    .collect();
+            }
+            let offset = self.blocks[b].1;
+            res.extend(
+                codes
+                    .into_iter()
+                    .filter_map(|c| self.representative(offset + c)),
+            );
+        }
+        res.sort_unstable();
+        res.dedup();
+        res
+    }
+}
+
+impl<V: VertexID> Graph for PowerGraph<V> {
+    type Vertex = u64;
+
+    fn vertices(&self) -> impl Iterator<Item = &u64> {
+        // A representative is its own root, so the entry is the code itself.
+        self.root
+            .iter()
+            .enumerate()
+            .filter(|&(code, &r)| r == code as u64)
+            .map(|(_, r)| r)
+    }
+
+    fn vertex_count(&self) -> usize {
+        self.count
+    }
+
+    fn has_vertex(&self, v: &u64) -> bool {
+        self.root.get(*v as usize) == Some(v)
+    }
+
+    fn edges(&self) -> impl Iterator<Item = (u64, u64)> {
+        Graph::vertices(self).flat_map(move |&u| {
+            self.neighbors(u, &self.out_rows)
+                .into_iter()
+                .map(move |v| (u, v))
+        })
+    }
+
+    fn has_edge(&self, u: &u64, v: &u64) -> bool {
+        // Decode the class of v once, the digits of its members are stored one
+        // after another, with the block and the start of each member.
+        let mut digits = Vec::new();
+        let targets = self
+            .class(*v)
+            .iter()
+            .map(|&b| {
+                let start = digits.len();
+                (self.decode_into(b, &mut digits), start)
+            })
+            .collect::<Vec<_>>();
+        let mut x = Vec::new();
+        self.class(*u).iter().any(|&a| {
+            x.clear();
+            let b0 = self.decode_into(a, &mut x);
+            targets.iter().any(|&(b1, start)| {
+                b0 == b1
+                    && x.iter()
+                        .zip(&digits[start..start + x.len()])
+                        .all(|(i, j)| self.out_rows[*i as usize].binary_search(j).is_ok())
+            })
+        })
+    }
+
+    fn out_neighbors(&self, v: &u64) -> impl Iterator<Item = u64> {
+        self.neighbors(*v, &self.out_rows).into_iter()
+    }
+
+    fn in_neighbors(&self, v: &u64) -> impl Iterator<Item = u64> {
+        self.neighbors(*v, &self.in_rows).into_iter()
+    }
+}
@@ -0,0 +1,1347 @@
+//! An incremental backtracking search for graph homomorphisms.
+//!
+//! The lists of all vertices are kept as one bitset over the dense indices of
+//! the target graph and arc consistency is maintained incrementally after each
+//! assignment. Every removal from a list is recorded on a trail, so that
+//! backtracking undoes exactly the removals of the abandoned steps instead of
+//! restoring copies of all lists.
+//!
+//! Each removal on the trail also records its reason. With backjumping, a
+//! conflict is explained by the assignments that its removals depend on, the
+//! search returns to the last of these assignments and remembers the
+//! combination as a nogood.
+//!
+//! The vertices of g0 are ordered by their ids, so that the search doesn't
+//! depend on the order in which the graph stores them. Ties of the variable
+//! and value orders are broken by this order, or randomly if a seed is given.
+//!
+//! A search can also run on rayon's thread pool, either by splitting its tree
+//! on the first branching vertices or by racing differently configured
+//! searches. In both cases the first solution cancels the other searches.
+//!
+//! After a solution the search can continue from where it stopped, which
+//! enumerates all solutions, optionally only one of each orbit under some
+//! automorphisms of g1.
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Display};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering as AtomicOrdering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::adjacency_list::{DenseID, VertexID};
+use crate::consistency::{List, Lists, Propagation};
+use crate::graph::Graph;
+use crate::metrics::{allocations, Metrics};
+
+/// The order in which a `Search` assigns the vertices of g0.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VariableOrder {
+    /// By increasing list size after preprocessing, fixed before the search
+    Static,
+    /// By increasing current list size
+    Dom,
+    /// By increasing ratio of current list size and degree
+    DomDeg,
+    /// By increasing ratio of current list size and weighted degree, where
+    /// the weight of an edge is one more than the number of times its revision
+    /// emptied a list
+    DomWdeg,
+    /// By increasing level in the component and then by current list size,
+    /// if g0 is balanced, otherwise like `Dom`
+    Level,
+}
+
+impl Display for VariableOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VariableOrder::Static => write!(f, "static"),
+            VariableOrder::Dom => write!(f, "dom"),
+            VariableOrder::DomDeg => write!(f, "dom-deg"),
+            VariableOrder::DomWdeg => write!(f, "dom-wdeg"),
+            VariableOrder::Level => write!(f, "level"),
+        }
+    }
+}
+
+impl FromStr for VariableOrder {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "static" => Ok(VariableOrder::Static),
+            "dom" => Ok(VariableOrder::Dom),
+            "dom-deg" => Ok(VariableOrder::DomDeg),
+            "dom-wdeg" => Ok(VariableOrder::DomWdeg),
+            "level" => Ok(VariableOrder::Level),
+            _ => Err("Unknown variable order"),
+        }
+    }
+}
+
+/// How a `Search` returns from an assignment that can't be extended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backtracking {
+    /// Returns to the last assignment
+    Chronological,
+    /// Returns to the last assignment that takes part in the conflict and
+    /// keeps up to `nogoods` of the conflicting combinations of assignments
+    Backjumping { nogoods: usize },
+}
+
+impl Display for Backtracking {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Backtracking::Chronological => write!(f, "chronological"),
+            Backtracking::Backjumping { nogoods } => write!(f, "cbj:{}", nogoods),
+        }
+    }
+}
+
+impl FromStr for Backtracking {
+    type Err = &'static str;
+
+    /// Parses "chronological", "cbj" or "cbj:N", where N is the maximum
+    /// number of nogoods, 1000 by default.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, ':');
+        match (parts.next(), parts.next()) {
+            (Some("chronological"), None) => Ok(Backtracking::Chronological),
+            (Some("cbj"), None) => Ok(Backtracking::Backjumping { nogoods: 1000 }),
+            (Some("cbj"), Some(n)) => match n.parse() {
+                Ok(nogoods) => Ok(Backtracking::Backjumping { nogoods }),
+                Err(_) => Err("Invalid number of nogoods"),
+            },
+            _ => Err("Unknown backtracking"),
+        }
+    }
+}
+
+/// When a `Search` abandons its assignments and starts again from the root.
+/// Learned nogoods and the weights of `VariableOrder::DomWdeg` are kept.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Restarts {
+    /// The search never restarts
+    None,
+    /// The i-th run ends after `base` times the i-th element of the Luby
+    /// sequence 1, 1, 2, 1, 1, 2, 4, 1, ... backtracks
+    Luby { base: u64 },
+    /// The i-th run ends after `base * factor^i` backtracks
+    Geometric { base: u64, factor: f64 },
+}
+
+impl Restarts {
+    // Returns the number of backtracks after which run `run` ends.
+    fn cutoff(&self, run: u32) -> u64 {
+        match *self {
+            Restarts::None => u64::MAX,
+            Restarts::Luby { base } => base.saturating_mul(luby(run as u64 + 1)),
+            Restarts::Geometric { base, factor } => (base as f64 * factor.powi(run as i32)) as u64,
+        }
+        .max(1)
+    }
+}
+
+impl Display for Restarts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Restarts::None => write!(f, "none"),
+            Restarts::Luby { base } => write!(f, "luby:{}", base),
+            Restarts::Geometric { base, factor } => write!(f, "geometric:{},{}", base, factor),
+        }
+    }
+}
+
+impl FromStr for Restarts {
+    type Err = &'static str;
+
+    /// Parses "none", "luby[:BASE]" or "geometric[:BASE[,FACTOR]]", where the
+    /// base is 100 and the factor 1.5 by default.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, ':');
+        let (policy, args) = (parts.next().unwrap(), parts.next());
+        let mut args = args.map(|a| a.split(',').collect::<Vec<_>>()).unwrap_or_default();
+        let factor = match (policy, args.len()) {
+            ("geometric", 2) => args.pop().unwrap().parse().map_err(|_| "Invalid factor")?,
+            _ => 1.5,
+        };
+        let base = match args.len() {
+            0 => 100,
+            1 => args[0].parse().map_err(|_| "Invalid base")?,
+            _ => return Err("Too many arguments"),
+        };
+        match (policy, factor > 1.0) {
+            ("none", _) if s == "none" => Ok(Restarts::None),
+            ("luby", _) => Ok(Restarts::Luby { base }),
+            ("geometric", true) => Ok(Restarts::Geometric { base, factor }),
+            ("geometric", false) => Err("The factor must be greater than 1"),
+            _ => Err("Unknown restarts"),
+        }
+    }
+}
+
+// Returns the i-th element of the Luby sequence, starting at i = 1.
+fn luby(mut i: u64) -> u64 {
+    loop {
+        let k = 64 - i.leading_zeros();
+        if i == (1 << k) - 1 {
+            return 1 << (k - 1);
+        }
+        i -= (1 << (k - 1)) - 1;
+    }
+}
+
+// A SplitMix64 generator, whose sequence only depends on the seed.
+#[derive(Clone)]
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // Returns a number in 0..n.
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+// A combination of assignments (x, a) that can't be extended to a solution.
+type Nogood = Arc<Vec<(usize, usize)>>;
+
+// Why a value was removed from a list.
+#[derive(Clone)]
+enum Reason {
+    // the vertex was assigned another value
+    Decision,
+    // the value had no support in the list of the vertex
+    Arc(usize),
+    // the value completes the nogood
+    Nogood(Nogood),
+}
+
+/// How a `Search` uses rayon's thread pool.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Parallelism {
+    /// The search runs on the calling thread
+    None,
+    /// The search tree is split on the first `depth` branching vertices and
+    /// the subtrees are searched in parallel, see `Search::run_parallel`
+    Split { depth: usize },
+    /// `searches` differently seeded and ordered searches race each other,
+    /// see `Search::race`
+    Portfolio { searches: usize },
+}
+
+impl Display for Parallelism {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Parallelism::None => write!(f, "none"),
+            Parallelism::Split { depth } => write!(f, "split:{}", depth),
+            Parallelism::Portfolio { searches } => write!(f, "portfolio:{}", searches),
+        }
+    }
+}
+
+impl FromStr for Parallelism {
+    type Err = &'static str;
+
+    /// Parses "none", "split[:DEPTH]" or "portfolio[:N]", where the depth is 2
+    /// and N the number of threads of rayon's pool by default.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, ':');
+        let (mode, arg) = (parts.next().unwrap(), parts.next());
+        let arg = match arg.map(str::parse::<usize>) {
+            Some(Ok(n)) if n > 0 => Some(n),
+            Some(_) => return Err("Invalid number"),
+            None => None,
+        };
+        match (mode, arg) {
+            ("none", None) => Ok(Parallelism::None),
+            ("split", depth) => Ok(Parallelism::Split {
+                depth: depth.unwrap_or(2),
+            }),
+            ("portfolio", searches) => Ok(Parallelism::Portfolio {
+                searches: searches.unwrap_or_else(rayon::current_num_threads),
+            }),
+            _ => Err("Unknown parallelism"),
+        }
+    }
+}
+
+// An assigned vertex of the depth-first search.
+#[derive(Clone)]
+struct Frame {
+    x: usize,
+    // the length of the trail before the assignment
+    mark: usize,
+    // with backjumping, the depths of the assignments that the failed values
+    // of x depend on
+    conflict: Vec<usize>,
+    // whether a solution was found below the assignment
+    solved: bool,
+}
+
+// The state of the depth-first search, kept between two solutions.
+#[derive(Clone)]
+struct Frontier {
+    order: Vec<usize>,
+    levels: Option<Vec<i64>>,
+    stack: Vec<Frame>,
+    // the values that are left to try for vertex x are kept in `values[x]`
+    values: Vec<u64>,
+    // the number of restarts and the backtracks before the last one
+    run: u32,
+    start: u32,
+    resume: bool,
+}
+
+/// The state of a backtracking search from `g0` to `g1`.
+#[derive(Clone)]
+pub struct Search<V0, V1> {
+    vars: Vec<V0>,
+    // words per bitset
+    words: usize,
+    // out- and in-neighbours of each vertex of g1, as bitsets
+    succs: Vec<u64>,
+    preds: Vec<u64>,
+    // An arc (x, y, true) requires a successor in the list of y for every
+    // vertex in the list of x, an arc (x, y, false) a predecessor
+    arcs: Vec<(usize, usize, bool)>,
+    // arcs to be revised, if the list of the respective vertex changed
+    items: Vec<Vec<usize>>,
+    lists: Vec<u64>,
+    sizes: Vec<usize>,
+    trail: Vec<(usize, usize, Reason)>,
+    pending_list: Vec<usize>,
+    pending: Vec<bool>,
+    propagation: Propagation,
+    order: VariableOrder,
+    // the value to try first for each vertex of g0
+    preferred: Vec<Option<usize>>,
+    // the number of times the revision of each edge emptied a list, plus one
+    weights: Vec<u64>,
+    assigned: Vec<bool>,
+    backtracking: Backtracking,
+    // the depth and value of each assigned vertex
+    decisions: Vec<(usize, usize)>,
+    // the vertex whose list was emptied by the last failed propagation
+    wiped: usize,
+    // a ring buffer of learned nogoods and the nogoods of each vertex
+    nogoods: Vec<Nogood>,
+    next_nogood: usize,
+    watches: Vec<Vec<usize>>,
+    // breaks ties of the variable and value orders, if seeded
+    rng: Option<Rng>,
+    seed: Option<u64>,
+    ranks: Vec<u64>,
+    restarts: Restarts,
+    // set when another search found a solution
+    stop: Option<Arc<AtomicBool>>,
+    frontier: Option<Frontier>,
+    // permutations of the indices of g1, only solutions that are the smallest
+    // of their images under these are produced
+    automorphisms: Vec<Vec<usize>>,
+    marker: std::marker::PhantomData<V1>,
+}
+
+impl<V0: VertexID, V1: DenseID> Search<V0, V1> {
+    /// Sets up the search from `g0` to `g1` on the given lists. If there's no
+    /// list specified for a vertex v, a list of all nodes of g1 is assigned to
+    /// v.
+    pub fn new<G0, G1>(g0: &G0, g1: &G1, lists: &Lists<V0, V1>) -> Search<V0, V1>
+    where
+        G0: Graph<Vertex = V0>,
+        G1: Graph<Vertex = V1>,
+    {
+        let mut vars = g0.vertices().cloned().collect::<Vec<_>>();
+        vars.sort_unstable();
+        let index = vars
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (v.clone(), i))
+            .collect::<HashMap<_, _>>();
+        let d = g1.vertices().map(|v| v.index() + 1).max().unwrap_or(0);
+        let words = d.div_ceil(64).max(1);
+
+        let mut succs = vec![0; d * words];
+        let mut preds = vec![0; d * words];
+        for (u, v) in g1.edges() {
+            set(&mut succs[u.index() * words..], v.index());
+            set(&mut preds[v.index() * words..], u.index());
+        }
+
+        let mut edges = g0.edges().map(|(u, v)| (index[&u], index[&v])).collect::<Vec<_>>();
+        edges.sort_unstable();
+        let mut arcs = Vec::new();
+        for (u, v) in edges {
+            arcs.push((u, v, true));
+            arcs.push((v, u, false));
+        }
+        let mut items = vec![Vec::new(); vars.len()];
+        for (i, &(_, y, _)) in arcs.iter().enumerate() {
+            items[y].push(i);
+        }
+
+        let mut all = vec![0; words];
+        for v in g1.vertices() {
+            set(&mut all, v.index());
+        }
+        let mut bits = vec![0; vars.len() * words];
+        for (x, v) in vars.iter().enumerate() {
+            let list = &mut bits[x * words..(x + 1) * words];
+            match lists.get(v) {
+                Some(l) => l.iter().filter(|w| get(&all, w.index())).for_each(|w| {
+                    set(list, w.index());
+                }),
+                None => list.copy_from_slice(&all),
+            }
+        }
+        let sizes = bits
+            .chunks(words)
+            .map(|l| l.iter().map(|w| w.count_ones() as usize).sum())
+            .collect();
+
+        Search {
+            pending: vec![false; vars.len()],
+            preferred: vec![None; vars.len()],
+            weights: vec![1; arcs.len() / 2],
+            assigned: vec![false; vars.len()],
+            backtracking: Backtracking::Chronological,
+            decisions: vec![(0, 0); vars.len()],
+            wiped: 0,
+            nogoods: Vec::new(),
+            next_nogood: 0,
+            watches: vec![Vec::new(); vars.len()],
+            rng: None,
+            seed: None,
+            ranks: (0..vars.len() as u64).collect(),
+            restarts: Restarts::None,
+            stop: None,
+            frontier: None,
+            automorphisms: Vec::new(),
+            pending_list: Vec::with_capacity(vars.len()),
+            trail: Vec::new(),
+            vars,
+            words,
+            succs,
+            preds,
+            arcs,
+            items,
+            lists: bits,
+            sizes,
+            propagation: Propagation::MAC,
+            order: VariableOrder::Static,
+            marker: std::marker::PhantomData,
+        }
+    }
+
+    /// The local consistency that is maintained after every assignment,
+    /// defaults to arc consistency.
+    pub fn propagation(mut self, propagation: Propagation) -> Self {
+        self.propagation = propagation;
+        self
+    }
+
+    /// How the search backtracks, defaults to `Backtracking::Chronological`.
+    pub fn backtracking(mut self, backtracking: Backtracking) -> Self {
+        self.backtracking = backtracking;
+        self
+    }
+
+    /// Returns an iterator over all solutions. The search doesn't restart
+    /// while enumerating, since it would repeat solutions.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use tripolys::adjacency_list::AdjacencyList;
+    /// use tripolys::consistency::Lists;
+    /// use tripolys::search::Search;
+    ///
+    /// let mut k2 = AdjacencyList::<u32>::new();
+    /// k2.add_vertex(0);
+    /// k2.add_vertex(1);
+    /// k2.add_edge(&0, &1);
+    /// k2.add_edge(&1, &0);
+    /// let mut path = AdjacencyList::<u32>::new();
+    /// for v in 0..3 {
+    ///     path.add_vertex(v);
+    /// }
+    /// path.add_edge(&0, &1);
+    /// path.add_edge(&1, &2);
+    ///
+    /// // a directed path has two homomorphisms to K2
+    /// assert_eq!(Search::new(&path, &k2, &Lists::new()).solutions().count(), 2);
+    /// ```
+    pub fn solutions(mut self) -> Solutions<V0, V1> {
+        self.restarts = Restarts::None;
+        Solutions {
+            search: self,
+            metrics: Metrics::new(),
+            done: false,
+        }
+    }
+
+    /// Counts the solutions, but stops at `limit` solutions if given. The
+    /// search doesn't restart while counting.
+    pub fn count(&mut self, limit: Option<u64>, metrics: &mut Metrics) -> u64 {
+        let search_start = Instant::now();
+        let allocations_start = allocations();
+        self.restarts = Restarts::None;
+        let mut count = 0;
+        while limit.is_none_or(|limit| count < limit) && self.solve(metrics) == Some(true) {
+            count += 1;
+        }
+        metrics.allocations = allocations() - allocations_start;
+        metrics.search_time = search_start.elapsed();
+        count
+    }
+
+    /// Produces only one solution f of each orbit of the automorphisms of g1
+    /// that map every list onto itself, acting by f ↦ σ ∘ f. The produced
+    /// solution is the lexicographically smallest of its orbit, in the order
+    /// of the vertices of g0.
+    pub fn modulo(mut self, automorphisms: &[HashMap<V1, V1>]) -> Self {
+        let d = self.succs.len() / self.words;
+        for automorphism in automorphisms {
+            let mut sigma = (0..d).collect::<Vec<_>>();
+            for (v, w) in automorphism {
+                sigma[v.index()] = w.index();
+            }
+            let preserves = |x: usize| bits(self.list(x)).all(|a| get(self.list(x), sigma[a]));
+            if (0..self.vars.len()).all(preserves) {
+                self.automorphisms.push(sigma);
+            }
+        }
+        self
+    }
+
+    /// Like `run`, but the search tree is split on the first `depth` branching
+    /// vertices and the subtrees are searched in parallel on rayon's thread
+    /// pool. The first solution cancels the remaining subtrees. The metrics
+    /// count the steps of all subtrees.
+    pub fn run_parallel(&mut self, depth: usize, metrics: &mut Metrics) -> Option<Lists<V0, V1>>
+    where
+        V0: Send + Sync,
+        V1: Send + Sync,
+    {
+        let search_start = Instant::now();
+        self.stop = Some(Arc::new(AtomicBool::new(false)));
+        let total = Mutex::new(Metrics::new());
+        let res = match self.prepare() {
+            Some((order, levels)) => self.split(depth, &order, levels.as_deref(), &total),
+            None => None,
+        };
+        add_counts(metrics, &total.into_inner().unwrap());
+        metrics.search_time = search_start.elapsed();
+        res
+    }
+
+    /// Runs the search with the parallelism `parallelism`, see `run`,
+    /// `run_parallel` and `race`. The searches of a portfolio use the variable
+    /// order and seed of this search in turn with other orders and seeds.
+    pub fn run_with(
+        &mut self,
+        parallelism: Parallelism,
+        metrics: &mut Metrics,
+    ) -> Option<Lists<V0, V1>>
+    where
+        V0: Send + Sync,
+        V1: Send + Sync,
+    {
+        match parallelism {
+            Parallelism::None => self.run(metrics),
+            Parallelism::Split { depth } => self.run_parallel(depth, metrics),
+            Parallelism::Portfolio { searches } => {
+                let orders = [
+                    self.order,
+                    VariableOrder::DomWdeg,
+                    VariableOrder::Dom,
+                    VariableOrder::DomDeg,
+                ];
+                let seed = self.seed.unwrap_or(0);
+                let searches = (0..searches)
+                    .map(|i| match i {
+                        0 => self.clone(),
+                        _ => self
+                            .clone()
+                            .variable_order(orders[i % orders.len()])
+                            .seed(seed + i as u64),
+                    })
+                    .collect();
+                Search::race(searches, metrics)
+            }
+        }
+    }
+
+    /// Runs `searches` in parallel on rayon's thread pool and returns the
+    /// result of the search that finishes first, the other searches are
+    /// cancelled. The searches should differ in their configuration, e.g. in
+    /// their seeds. The metrics count the steps of the finished search and the
+    /// allocations of all searches.
+    pub fn race(searches: Vec<Search<V0, V1>>, metrics: &mut Metrics) -> Option<Lists<V0, V1>>
+    where
+        V0: Send + Sync,
+        V1: Send + Sync,
+    {
+        let search_start = Instant::now();
+        let stop = Arc::new(AtomicBool::new(false));
+        let allocated = AtomicU64::new(0);
+        let winner = searches.into_par_iter().find_map_any(|mut search| {
+            search.stop = Some(Arc::clone(&stop));
+            let mut metrics = Metrics::new();
+            let allocations_start = allocations();
+            let found = search.solve(&mut metrics);
+            allocated.fetch_add(allocations() - allocations_start, AtomicOrdering::Relaxed);
+            let found = found?;
+            stop.store(true, AtomicOrdering::Relaxed);
+            Some((found.then(|| search.lists()), metrics))
+        });
+        metrics.search_time = search_start.elapsed();
+        metrics.allocations += allocated.into_inner();
+        let (res, winner) = winner?;
+        add_counts(metrics, &winner);
+        res
+    }
+
+    /// Breaks ties of the variable and value orders randomly, with a sequence
+    /// that only depends on `seed`. Without a seed, the search is
+    /// deterministic and ties are broken by the ids of the vertices.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.rng = Some(Rng(seed));
+        self.seed = Some(seed);
+        self.shuffle();
+        self
+    }
+
+    /// When the search restarts, defaults to `Restarts::None`. Every restart
+    /// breaks the ties of the variable and value orders anew, see `seed`. An
+    /// unseeded search does so with the seed 0 from its first restart on, since
+    /// otherwise each run would repeat the previous one.
+    pub fn restarts(mut self, restarts: Restarts) -> Self {
+        self.restarts = restarts;
+        self
+    }
+
+    /// The order in which the vertices of g0 are assigned, defaults to
+    /// `VariableOrder::Static`.
+    pub fn variable_order(mut self, order: VariableOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// The value to try first for each vertex of g0, the other values are
+    /// tried in order of increasing index.
+    pub fn prefer(mut self, f: impl Fn(&V0) -> Option<V1>) -> Self {
+        for (x, v) in self.vars.iter().enumerate() {
+            self.preferred[x] = f(v).map(|w| w.index());
+        }
+        self
+    }
+
+    /// Performs a depth-first-search for a mapping from `g0` to `g1` that is
+    /// locally consistent after every assignment. Returns the singleton lists
+    /// of the mapping, or None if there is none.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use tripolys::adjacency_list::AdjacencyList;
+    /// use tripolys::consistency::{List, Lists};
+    /// use tripolys::metrics::Metrics;
+    /// use tripolys::search::Search;
+    ///
+    /// let mut k2 = AdjacencyList::<u32>::new();
+    /// k2.add_vertex(0);
+    /// k2.add_vertex(1);
+    /// k2.add_edge(&0, &1);
+    /// let mut g = AdjacencyList::<u32>::new();
+    /// for v in 0..3 {
+    ///     g.add_vertex(v);
+    /// }
+    /// g.add_edge(&0, &1);
+    ///
+    /// // the isolated vertex 2 has an empty list
+    /// let mut lists = Lists::new();
+    /// lists.insert(2, List::new());
+    /// let mut metrics = Metrics::default();
+    /// assert!(Search::new(&g, &k2, &lists).run(&mut metrics).is_none());
+    /// assert_eq!(Search::new(&g, &k2, &lists).count(None, &mut metrics), 0);
+    /// ```
+    pub fn run(&mut self, metrics: &mut Metrics) -> Option<Lists<V0, V1>> {
+        let search_start = Instant::now();
+        let allocations_start = allocations();
+        let found = self.solve(metrics);
+        metrics.allocations = allocations() - allocations_start;
+        metrics.search_time = search_start.elapsed();
+
+        if found == Some(true) {
+            Some(self.lists())
+        } else {
+            None
+        }
+    }
+
+    // Establishes the local consistency on the current lists and returns the
+    // static variable order and the levels for the level order. Returns None,
+    // if a list is empty or an empty list is derived.
+    fn prepare(&mut self) -> Option<(Vec<usize>, Option<Vec<i64>>)> {
+        // Variables without arcs are never propagated, and the search skips
+        // variables with at most one value.
+        if self.sizes.contains(&0) {
+            return None;
+        }
+        for x in 0..self.vars.len() {
+            self.enqueue(x);
+        }
+        if !self.propagate() {
+            return None;
+        }
+
+        let mut order = (0..self.vars.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&x| (self.sizes[x], self.ranks[x]));
+        let levels = match self.order {
+            VariableOrder::Level => self.levels(),
+            _ => None,
+        };
+        Some((order, levels))
+    }
+
+    // Returns whether there is a solution, or None if the search was
+    // cancelled. After a solution, the next call continues the search with the
+    // next solution.
+    fn solve(&mut self, metrics: &mut Metrics) -> Option<bool> {
+        let mut frontier = match self.frontier.take() {
+            Some(frontier) => frontier,
+            None => match self.prepare() {
+                Some((order, levels)) => Frontier {
+                    values: vec![0; order.len() * self.words],
+                    stack: Vec::with_capacity(order.len()),
+                    order,
+                    levels,
+                    run: 0,
+                    start: metrics.backtracked,
+                    resume: false,
+                },
+                None => return Some(false),
+            },
+        };
+        loop {
+            let found = self.descend(&mut frontier, metrics);
+            if found != Some(true) {
+                return found;
+            }
+            if self.is_smallest() {
+                self.frontier = Some(frontier);
+                return found;
+            }
+        }
+    }
+
+    // Returns true, if the current solution is lexicographically not larger
+    // than its images under the automorphisms.
+    fn is_smallest(&self) -> bool {
+        let value = |x: usize| bits(self.list(x)).next().unwrap();
+        self.automorphisms.iter().all(|sigma| {
+            for x in 0..self.vars.len() {
+                let a = value(x);
+                match sigma[a].cmp(&a) {
+                    Ordering::Less => return false,
+                    Ordering::Greater => return true,
+                    Ordering::Equal => {}
+                }
+            }
+            true
+        })
+    }
+
+    // Descends from the frontier to the next solution.
+    fn descend(&mut self, f: &mut Frontier, metrics: &mut Metrics) -> Option<bool> {
+        let words = self.words;
+        let backjumping = self.backtracking != Backtracking::Chronological;
+        loop {
+            // After a solution, the search continues with the next value of the
+            // last vertex
+            if !std::mem::take(&mut f.resume) {
+                let x = match self.select(&f.order, f.levels.as_deref()) {
+                    Some(x) => x,
+                    None => {
+                        for frame in &mut f.stack {
+                            frame.solved = true;
+                        }
+                        f.resume = true;
+                        return Some(true);
+                    }
+                };
+                f.values[x * words..(x + 1) * words].copy_from_slice(self.list(x));
+                self.assigned[x] = true;
+                f.stack.push(Frame {
+                    x,
+                    mark: self.trail.len(),
+                    conflict: Vec::new(),
+                    solved: false,
+                });
+            }
+
+            // Try the remaining values of the last vertex, backtrack if there
+            // are none
+            loop {
+                let (x, mark) = match f.stack.last() {
+                    Some(frame) => (frame.x, frame.mark),
+                    None => return Some(false),
+                };
+                if self.stop.as_ref().is_some_and(|s| s.load(AtomicOrdering::Relaxed)) {
+                    return None;
+                }
+                if (metrics.backtracked - f.start) as u64 >= self.restarts.cutoff(f.run) {
+                    // Start again from the root with new ties
+                    self.undo(f.stack[0].mark);
+                    for frame in f.stack.drain(..) {
+                        self.assigned[frame.x] = false;
+                    }
+                    self.rng.get_or_insert(Rng(0));
+                    self.shuffle();
+                    f.order.sort_by_key(|&x| (self.sizes[x], self.ranks[x]));
+                    f.run += 1;
+                    f.start = metrics.backtracked;
+                    metrics.restarts += 1;
+                    break;
+                }
+                self.undo(mark);
+                let depth = f.stack.len() - 1;
+
+                let values = &mut f.values[x * words..(x + 1) * words];
+                let value = match (self.preferred[x], &mut self.rng) {
+                    (Some(a), _) if get(values, a) => {
+                        values[a / 64] &= !(1 << (a % 64));
+                        Some(a)
+                    }
+                    (_, Some(rng)) => {
+                        let count = values.iter().map(|w| w.count_ones() as usize).sum();
+                        match count {
+                            0 => None,
+                            _ => take(values, rng.below(count)),
+                        }
+                    }
+                    (_, None) => pop(values),
+                };
+                if let Some(a) = value {
+                    metrics.steps += 1;
+                    self.decisions[x] = (depth, a);
+                    self.assign(x, a);
+                    if self.apply_nogoods(x, a) && self.propagate() {
+                        break;
+                    }
+                    if backjumping {
+                        let conflict = self.explain(&f.stack, self.wiped, self.trail.len());
+                        if conflict.last() != Some(&depth) {
+                            // The conflict doesn't depend on x, no other value
+                            // can help
+                            if !self.jump(&mut f.stack, conflict, metrics) {
+                                return Some(false);
+                            }
+                        } else {
+                            let frame = &mut f.stack[depth];
+                            merge(&mut frame.conflict, &conflict[..conflict.len() - 1]);
+                        }
+                    }
+                } else if backjumping && !f.stack[depth].solved {
+                    // Values removed before the assignment of x are explained
+                    // by their removals
+                    let mut conflict = self.explain(&f.stack, x, mark);
+                    merge(&mut conflict, &f.stack[depth].conflict);
+                    if !self.jump(&mut f.stack, conflict, metrics) {
+                        return Some(false);
+                    }
+                } else {
+                    // The values of a vertex with a solution below aren't
+                    // explained by a conflict
+                    f.stack.pop();
+                    self.assigned[x] = false;
+                    metrics.backtracked += 1;
+                }
+            }
+        }
+    }
+
+    // Searches the subtrees below the first `depth` branching vertices in
+    // parallel and adds the counts of all subtrees to `total`.
+    fn split(
+        &self,
+        depth: usize,
+        order: &[usize],
+        levels: Option<&[i64]>,
+        total: &Mutex<Metrics>,
+    ) -> Option<Lists<V0, V1>>
+    where
+        V0: Send + Sync,
+        V1: Send + Sync,
+    {
+        let stop = self.stop.as_ref().unwrap();
+        let x = match self.select(order, levels) {
+            Some(x) if depth > 0 => x,
+            _ => {
+                let allocations_start = allocations();
+                let mut leaf = self.clone();
+                let mut metrics = Metrics::new();
+                let found = leaf.solve(&mut metrics);
+                metrics.allocations = allocations() - allocations_start;
+                add_counts(&mut total.lock().unwrap(), &metrics);
+                if found != Some(true) {
+                    return None;
+                }
+                stop.store(true, AtomicOrdering::Relaxed);
+                return Some(leaf.lists());
+            }
+        };
+
+        bits(self.list(x))
+            .collect::<Vec<_>>()
+            .into_par_iter()
+            .find_map_any(|a| {
+                if stop.load(AtomicOrdering::Relaxed) {
+                    return None;
+                }
+                let allocations_start = allocations();
+                let mut child = self.clone();
+                child.assigned[x] = true;
+                child.decisions[x] = (0, a);
+                child.assign(x, a);
+                let consistent = child.propagate();
+                {
+                    let mut total = total.lock().unwrap();
+                    total.steps += 1;
+                    total.allocations += allocations() - allocations_start;
+                }
+                if !consistent {
+                    return None;
+                }
+                child.split(depth - 1, order, levels, total)
+            })
+    }
+
+    // Returns to the last assignment of `conflict`, which holds the sorted
+    // depths of assignments that can't be extended together, and learns them
+    // as a nogood. Returns false, if the conflict holds no assignment.
+    fn jump(
+        &mut self,
+        stack: &mut Vec<Frame>,
+        mut conflict: Vec<usize>,
+        metrics: &mut Metrics,
+    ) -> bool {
+        metrics.backtracked += 1;
+        let target = match conflict.pop() {
+            Some(d) => d,
+            None => return false,
+        };
+        let nogood = conflict
+            .iter()
+            .chain(Some(&target))
+            .map(|&d| (stack[d].x, self.decisions[stack[d].x].1))
+            .collect();
+        self.learn(nogood, metrics);
+
+        if stack.len() - target > 2 {
+            metrics.jumps += 1;
+        }
+        while stack.len() > target + 1 {
+            let frame = stack.pop().unwrap();
+            self.assigned[frame.x] = false;
+        }
+        merge(&mut stack[target].conflict, &conflict);
+        true
+    }
+
+    // Returns the sorted depths of the assignments that the removals from the
+    // list of x before position `end` of the trail depend on.
+    fn explain(&self, stack: &[Frame], x: usize, end: usize) -> Vec<usize> {
+        // All removals from the list of y before position need[y] are part of
+        // the explanation
+        let mut need = vec![0; self.vars.len()];
+        let mut depths = vec![false; stack.len()];
+        need[x] = end;
+        let root = stack.first().map_or(end, |frame| frame.mark);
+        for i in (root..end).rev() {
+            let (y, _, ref reason) = self.trail[i];
+            if i >= need[y] {
+                continue;
+            }
+            match reason {
+                Reason::Decision => depths[self.decisions[y].0] = true,
+                Reason::Arc(z) => need[*z] = need[*z].max(i),
+                Reason::Nogood(nogood) => {
+                    for &(z, _) in nogood.iter().filter(|&&(z, _)| z != y) {
+                        depths[self.decisions[z].0] = true;
+                    }
+                }
+            }
+        }
+        (0..depths.len()).filter(|&d| depths[d]).collect()
+    }
+
+    // Adds a nogood to the store, replacing the oldest one if it is full.
+    fn learn(&mut self, nogood: Vec<(usize, usize)>, metrics: &mut Metrics) {
+        let limit = match self.backtracking {
+            Backtracking::Backjumping { nogoods } => nogoods,
+            Backtracking::Chronological => 0,
+        };
+        if limit == 0 {
+            return;
+        }
+        let id = self.next_nogood;
+        self.next_nogood = (id + 1) % limit;
+        if id < self.nogoods.len() {
+            for &(x, _) in self.nogoods[id].iter() {
+                self.watches[x].retain(|&other| other != id);
+            }
+        }
+        for &(x, _) in &nogood {
+            self.watches[x].push(id);
+        }
+        if id < self.nogoods.len() {
+            self.nogoods[id] = Arc::new(nogood);
+        } else {
+            self.nogoods.push(Arc::new(nogood));
+        }
+        metrics.nogoods += 1;
+    }
+
+    // Removes the values that would complete a nogood after the assignment of
+    // `a` to x. Returns false, if an empty list is derived.
+    fn apply_nogoods(&mut self, x: usize, a: usize) -> bool {
+        for i in 0..self.watches[x].len() {
+            let nogood = Arc::clone(&self.nogoods[self.watches[x][i]]);
+            if !nogood.contains(&(x, a)) {
+                continue;
+            }
+            let mut open = nogood
+                .iter()
+                .filter(|&&(y, b)| !self.assigned[y] || self.decisions[y].1 != b);
+            let (y, b) = match (open.next(), open.next()) {
+                (None, _) => (x, a),
+                (Some(&(y, b)), None) if !self.assigned[y] => (y, b),
+                _ => continue,
+            };
+            if get(self.list(y), b) {
+                self.remove(y, b, Reason::Nogood(nogood));
+                if self.sizes[y] == 0 {
+                    self.wiped = y;
+                    for z in self.pending_list.drain(..) {
+                        self.pending[z] = false;
+                    }
+                    return false;
+                }
+                self.enqueue(y);
+            }
+        }
+        true
+    }
+
+    // Assigns new random ranks to the vertices, if seeded.
+    fn shuffle(&mut self) {
+        if let Some(rng) = &mut self.rng {
+            for rank in &mut self.ranks {
+                *rank = rng.next();
+            }
+        }
+    }
+
+    // Returns the next vertex to assign according to the variable order, or
+    // None if all vertices are assigned. With forward checking, the edges of a
+    // vertex are only checked when it is assigned, so vertices with a single
+    // value can't be skipped.
+    fn select(&self, order: &[usize], levels: Option<&[i64]>) -> Option<usize> {
+        let forward_checking = self.propagation == Propagation::ForwardChecking;
+        let open = |x: &usize| !self.assigned[*x] && (forward_checking || self.sizes[*x] > 1);
+        let candidates = (0..self.vars.len()).filter(open);
+        let ratio = |x: usize, y: usize, deg: &dyn Fn(usize) -> u64| -> Ordering {
+            (self.sizes[x] as u64 * deg(y))
+                .cmp(&(self.sizes[y] as u64 * deg(x)))
+                .then(self.ranks[x].cmp(&self.ranks[y]))
+        };
+        let deg = |x: usize| self.items[x].len().max(1) as u64;
+        let wdeg = |x: usize| self.items[x].iter().map(|&i| self.weights[i / 2]).sum();
+
+        match (self.order, levels) {
+            (VariableOrder::Static, _) => order.iter().copied().find(open),
+            (VariableOrder::DomDeg, _) => candidates.min_by(|&x, &y| ratio(x, y, &deg)),
+            (VariableOrder::DomWdeg, _) => candidates.min_by(|&x, &y| ratio(x, y, &wdeg)),
+            (VariableOrder::Level, Some(levels)) => {
+                candidates.min_by_key(|&x| (levels[x], self.sizes[x], self.ranks[x]))
+            }
+            _ => candidates.min_by_key(|&x| (self.sizes[x], self.ranks[x])),
+        }
+    }
+
+    // Returns the level of each vertex of g0 in its component, such that the
+    // lowest level of every component is 0. Returns None, if g0 is not
+    // balanced.
+    fn levels(&self) -> Option<Vec<i64>> {
+        let mut levels = vec![None; self.vars.len()];
+        let mut component = Vec::new();
+        for root in 0..self.vars.len() {
+            if levels[root].is_some() {
+                continue;
+            }
+            levels[root] = Some(0);
+            component.clear();
+            component.push(root);
+            let mut i = 0;
+            while i < component.len() {
+                let y = component[i];
+                i += 1;
+                for &arc in &self.items[y] {
+                    let (x, _, forward) = self.arcs[arc];
+                    let level = levels[y].unwrap() + if forward { -1 } else { 1 };
+                    match levels[x] {
+                        None => {
+                            levels[x] = Some(level);
+                            component.push(x);
+                        }
+                        Some(l) if l != level => return None,
+                        Some(_) => {}
+                    }
+                }
+            }
+            let min = component.iter().map(|&x| levels[x].unwrap()).min().unwrap();
+            for &x in &component {
+                levels[x] = levels[x].map(|l| l - min);
+            }
+        }
+        Some(levels.into_iter().map(|l| l.unwrap()).collect())
+    }
+
+    fn list(&self, x: usize) -> &[u64] {
+        &self.lists[x * self.words..(x + 1) * self.words]
+    }
+
+    fn enqueue(&mut self, x: usize) {
+        if !self.pending[x] {
+            self.pending[x] = true;
+            self.pending_list.push(x);
+        }
+    }
+
+    // Removes `a` from the list of x and records it on the trail.
+    fn remove(&mut self, x: usize, a: usize, reason: Reason) {
+        self.lists[x * self.words + a / 64] &= !(1 << (a % 64));
+        self.sizes[x] -= 1;
+        self.trail.push((x, a, reason));
+    }
+
+    // Restores the lists to the state when the trail had length `mark`.
+    fn undo(&mut self, mark: usize) {
+        while self.trail.len() > mark {
+            let (x, a, _) = self.trail.pop().unwrap();
+            self.lists[x * self.words + a / 64] |= 1 << (a % 64);
+            self.sizes[x] += 1;
+        }
+    }
+
+    // Reduces the list of x to `a`.
+    fn assign(&mut self, x: usize, a: usize) {
+        for w in 0..self.words {
+            let mut word = self.lists[x * self.words + w];
+            while word != 0 {
+                let b = w * 64 + word.trailing_zeros() as usize;
+                word &= word - 1;
+                if b != a {
+                    self.remove(x, b, Reason::Decision);
+                }
+            }
+        }
+        self.enqueue(x);
+    }
+
+    // Revises the arcs of all pending vertices. With arc consistency, vertices
+    // whose list changed become pending until the lists are arc-consistent.
+    // Returns false, if an empty list is derived.
+    fn propagate(&mut self) -> bool {
+        while let Some(y) = self.pending_list.pop() {
+            self.pending[y] = false;
+            for i in 0..self.items[y].len() {
+                let arc = self.items[y][i];
+                let (x, y, forward) = self.arcs[arc];
+                if self.revise(x, y, forward) {
+                    // list of x changed, was the empty list derived?
+                    if self.sizes[x] == 0 {
+                        self.weights[arc / 2] += 1;
+                        self.wiped = x;
+                        for z in self.pending_list.drain(..) {
+                            self.pending[z] = false;
+                        }
+                        return false;
+                    }
+                    if self.propagation == Propagation::MAC {
+                        self.enqueue(x);
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    // Removes all vertices from the list of x that have no successor
+    // (predecessor if not `forward`) in the list of y. Returns true, if the
+    // list of x was reduced.
+    fn revise(&mut self, x: usize, y: usize, forward: bool) -> bool {
+        let words = self.words;
+        let mut changed = false;
+        for w in 0..words {
+            let mut word = self.lists[x * words + w];
+            while word != 0 {
+                let a = w * 64 + word.trailing_zeros() as usize;
+                word &= word - 1;
+                let neighbors = if forward { &self.succs } else { &self.preds };
+                let neighbors = &neighbors[a * words..(a + 1) * words];
+                let list = &self.lists[y * words..(y + 1) * words];
+                if neighbors.iter().zip(list).all(|(n, l)| n & l == 0) {
+                    self.remove(x, a, Reason::Arc(y));
+                    changed = true;
+                }
+            }
+        }
+        changed
+    }
+
+    // The current lists, converted back to the vertices of g0 and g1.
+    fn lists(&self) -> Lists<V0, V1> {
+        self.vars
+            .iter()
+            .enumerate()
+            .map(|(x, v)| (v.clone(), bits(self.list(x)).map(V1::from_index).collect::<List<_>>()))
+            .collect()
+    }
+}
+
+/// An iterator over the solutions of a `Search`, see `Search::solutions`.
+pub struct Solutions<V0, V1> {
+    search: Search<V0, V1>,
+    metrics: Metrics,
+    done: bool,
+}
+
+impl<V0, V1> Solutions<V0, V1> {
+    /// The metrics of the search so far.
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+}
+
+impl<V0: VertexID, V1: DenseID> Iterator for Solutions<V0, V1> {
+    type Item = Lists<V0, V1>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        if self.search.solve(&mut self.metrics) == Some(true) {
+            Some(self.search.lists())
+        } else {
+            self.done = true;
+            None
+        }
+    }
+}
+
+/// Returns the automorphisms of `g`, i.e. the bijective homomorphisms from `g`
+/// to itself.
+pub fn automorphisms<V, G>(g: &G) -> Vec<HashMap<V, V>>
+where
+    G: Graph<Vertex = V>,
+    V: DenseID,
+{
+    // An automorphism preserves in- and out-degrees
+    let degrees = |v: &V| (g.in_neighbors(v).count(), g.out_neighbors(v).count());
+    let lists = g
+        .vertices()
+        .map(|v| {
+            let list = g.vertices().filter(|w| degrees(v) == degrees(w)).cloned();
+            (v.clone(), list.collect())
+        })
+        .collect();
+    Search::new(g, g, &lists)
+        .solutions()
+        .map(|f| {
+            f.into_iter()
+                .map(|(v, list)| (v, list.iter().next().unwrap()))
+                .collect::<HashMap<_, _>>()
+        })
+        .filter(|f| f.values().collect::<HashSet<_>>().len() == f.len())
+        .collect()
+}
+
+// Adds the counts of the search `other` to `metrics`.
+fn add_counts(metrics: &mut Metrics, other: &Metrics) {
+    metrics.backtracked += other.backtracked;
+    metrics.steps += other.steps;
+    metrics.jumps += other.jumps;
+    metrics.nogoods += other.nogoods;
+    metrics.restarts += other.restarts;
+    metrics.allocations += other.allocations;
+}
+
+// Adds the depths of `other` to the sorted depths of `conflict`.
+fn merge(conflict: &mut Vec<usize>, other: &[usize]) {
+    conflict.extend_from_slice(other);
+    conflict.sort_unstable();
+    conflict.dedup();
+}
+
+fn get(bits: &[u64], i: usize) -> bool {
+    bits[i / 64] & (1 << (i % 64)) != 0
+}
+
+fn set(bits: &mut [u64], i: usize) {
+    bits[i / 64] |= 1 << (i % 64);
+}
+
+// Removes the smallest index from the bitset and returns it.
+fn pop(bits: &mut [u64]) -> Option<usize> {
+    let w = bits.iter().position(|&w| w != 0)?;
+    let i = bits[w].trailing_zeros() as usize;
+    bits[w] &= bits[w] - 1;
+    Some(w * 64 + i)
+}
+
+// Removes the n-th smallest index from the bitset and returns it.
+fn take(bits: &mut [u64], mut n: usize) -> Option<usize> {
+    for (w, word) in bits.iter_mut().enumerate() {
+        let ones = word.count_ones() as usize;
+        if n >= ones {
+            n -= ones;
+            continue;
+        }
+        let mut rest = *word;
+        for _ in 0..n {
+            rest &= rest - 1;
+        }
+        let i = rest.trailing_zeros() as usize;
+        *word &= !(1 << i);
+        return Some(w * 64 + i);
+    }
+    None
+}
+
+// An iterator over the indices of the set bits.
+fn bits(bits: &[u64]) -> impl Iterator<Item = usize> + '_ {
+    bits.iter().enumerate().flat_map(|(w, &word)| {
+        let mut word = word;
+        std::iter::from_fn(move || {
+            if word == 0 {
+                return None;
+            }
+            let i = word.trailing_zeros() as usize;
+            word &= word - 1;
+            Some(w * 64 + i)
+        })
+    })
+}
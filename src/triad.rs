@@ -1,16 +1,26 @@
 //! The simplest form of an orientation of a tree that is not a path.
 use std::{
-    cmp::min,
+    cmp::{min, Ordering},
     collections::HashSet,
     convert::TryFrom,
+    error::Error,
     fmt, fs,
-    hash::Hash,
-    io::{self, Write},
+    hash::{Hash, Hasher},
+    io,
     str::FromStr,
     sync::Mutex,
 };
 
-use crate::{adjacency_list::AdjacencyList, configuration::Globals, list};
+use crate::{
+    adjacency_list::AdjacencyList,
+    cache_file::{
+        self, read_bits, read_varint, write_bits, write_varint, CacheError, CacheFile, Entry,
+        Format,
+    },
+    configuration::Globals,
+    dense_graph::DenseGraph,
+    list,
+};
 use rayon::prelude::*;
 
 use super::consistency::{ac_3, ac_3_lists, Lists};
@@ -24,7 +34,10 @@ use super::consistency::{ac_3, ac_3_lists, Lists};
 /// Note that we don't restrict the triad to have exactly three arms.
 /// Instead there must be at most three arms, and every triad that has less
 /// can be considered a "partial triad".
-#[derive(Debug, Clone, Hash, Default)]
+///
+/// Triads are compared and hashed by their canonical form, so two triads that
+/// only differ by the order of their arms are equal.
+#[derive(Debug, Clone, Default)]
 pub struct Triad(Vec<String>);
 
 impl Triad {
@@ -43,8 +56,39 @@ impl Triad {
         self.0.push(String::from(arm));
     }
 
+    /// Sorts the arms into canonical order: longer arms come first, arms of
+    /// the same length are ordered lexicographically.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tripolys::triad::Triad;
+    ///
+    /// let mut triad = Triad::from_strs("0", "11", "1");
+    /// triad.canonicalize();
+    /// assert_eq!(triad.to_string(), "11_0_1");
+    /// assert_eq!(triad, Triad::from_strs("1", "0", "11"));
+    /// ```
+    pub fn canonicalize(&mut self) {
+        self.0.sort_by(|a, b| arm_order(a, b));
+    }
+
+    // Returns the arms in canonical order and their number. A triad has at
+    // most three arms, so they are sorted on the stack and comparing or
+    // hashing triads doesn't allocate.
+    fn canonical_arms(&self) -> ([&str; 3], usize) {
+        let mut arms = [""; 3];
+        for (slot, arm) in arms.iter_mut().zip(&self.0) {
+            *slot = arm;
+        }
+        let n = self.0.len();
+        arms[..n].sort_unstable_by(|a, b| arm_order(a, b));
+        (arms, n)
+    }
+
     pub fn is_core(&self) -> bool {
-        for (_, v) in ac_3(&self.into(), &self.into()).unwrap() {
+        let graph = AdjacencyList::<u32>::from(self);
+        for (_, v) in ac_3(&graph, &graph).unwrap() {
             if v.size() != 1 {
                 return false;
             }
@@ -71,46 +115,163 @@ fn ac3_precolour_0(g0: &AdjacencyList<u32>, g1: &AdjacencyList<u32>) -> Option<L
     ac_3_lists(g0, g1, lists)
 }
 
+// The canonical order of arms
+fn arm_order(a: &str, b: &str) -> Ordering {
+    b.len().cmp(&a.len()).then_with(|| a.cmp(b))
+}
+
+impl PartialEq for Triad {
+    fn eq(&self, other: &Self) -> bool {
+        let ((a, n), (b, m)) = (self.canonical_arms(), other.canonical_arms());
+        a[..n] == b[..m]
+    }
+}
+
+impl Eq for Triad {}
+
+impl Hash for Triad {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let (arms, n) = self.canonical_arms();
+        arms[..n].hash(state);
+    }
+}
+
+impl PartialOrd for Triad {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Triad {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let ((a, n), (b, m)) = (self.canonical_arms(), other.canonical_arms());
+        a[..n]
+            .iter()
+            .zip(&b[..m])
+            .map(|(x, y)| arm_order(x, y))
+            .find(|o| *o != Ordering::Equal)
+            .unwrap_or_else(|| n.cmp(&m))
+    }
+}
+
 impl fmt::Display for Triad {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let mut s = String::new();
-        for (i, arm) in self.0.iter().enumerate() {
-            if i > 0 {
-                s.push('_');
+        write!(f, "{}", self.to_string_with(Notation::Underscore))
+    }
+}
+
+/// The notations in which a triad can be written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Notation {
+    /// Arms separated by commas, e.g. `0111,00,1`
+    Comma,
+    /// Arms separated by underscores, e.g. `0111_00_1`. This is the notation of
+    /// `Display`.
+    Underscore,
+    /// The edges of the triad, with vertices labeled as in its
+    /// `AdjacencyList`, e.g. `0->1, 1->2, ...`
+    EdgeList,
+}
+
+impl Triad {
+    /// Returns the triad written in the notation `notation`. The comma and the
+    /// underscore notation are parsed back by `FromStr`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tripolys::triad::{Notation, Triad};
+    ///
+    /// let triad = Triad::from_strs("01", "1", "0");
+    /// assert_eq!(triad.to_string_with(Notation::Comma), "01,1,0");
+    /// assert_eq!(triad.to_string_with(Notation::EdgeList), "0->1, 2->1, 3->0, 0->4");
+    /// assert_eq!(triad.to_string_with(Notation::Underscore).parse(), Ok(triad));
+    /// ```
+    pub fn to_string_with(&self, notation: Notation) -> String {
+        match notation {
+            Notation::Comma => self.0.join(","),
+            Notation::Underscore => self.0.join("_"),
+            Notation::EdgeList => {
+                let mut edges = Vec::new();
+                let mut node_id = 1;
+                for arm in &self.0 {
+                    for (j, v) in arm.chars().enumerate() {
+                        let prev = if j == 0 { 0 } else { node_id - 1 };
+                        if v == '1' {
+                            edges.push(format!("{}->{}", node_id, prev));
+                        } else {
+                            edges.push(format!("{}->{}", prev, node_id));
+                        }
+                        node_id += 1;
+                    }
+                }
+                edges.join(", ")
+            }
+        }
+    }
+}
+
+/// An error which can be returned when parsing a triad.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseTriadError {
+    /// The string is empty
+    Empty,
+    /// The string doesn't contain exactly three arms
+    ArmCount(usize),
+    /// An arm contains a character other than `0` and `1`
+    InvalidCharacter(char),
+}
+
+impl fmt::Display for ParseTriadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseTriadError::Empty => write!(f, "Empty triad"),
+            ParseTriadError::ArmCount(n) => write!(f, "Expected 3 arms, found {}", n),
+            ParseTriadError::InvalidCharacter(c) => {
+                write!(f, "Invalid character {:?}, only 0s and 1s allowed", c)
             }
-            s.push_str(arm);
         }
-        write!(f, "{}", s)
     }
 }
 
+impl Error for ParseTriadError {}
+
+/// Parses a triad whose arms are separated by commas, underscores or
+/// whitespace, e.g. `0111,00,1`, `0111_00_1` or `0111 00 1`. Whitespace around
+/// the separators and quotes around the triad are ignored.
 impl FromStr for Triad {
-    type Err = &'static str;
+    type Err = ParseTriadError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let arms: Vec<String> = s.split(',').map(|x| x.into()).collect();
-        if arms.len() > 3 {
-            return Err("Too many arms were given!");
-        }
-        for arm in &arms {
-            if !arm.is_empty() {
-                for v in arm.chars().map(|c| c == '0' || c == '1') {
-                    if !v {
-                        return Err("Only 0s and 1s allowed!");
-                    }
-                }
+        let mut s = s.trim();
+        for quote in &['"', '\''] {
+            if s.len() >= 2 && s.starts_with(*quote) && s.ends_with(*quote) {
+                s = s[1..s.len() - 1].trim();
             }
         }
+        if s.is_empty() {
+            return Err(ParseTriadError::Empty);
+        }
 
-        if let Some(arm1) = arms.get(0) {
-            if let Some(arm2) = arms.get(1) {
-                if let Some(arm3) = arms.get(2) {
-                    return Ok(Triad::from_strs(arm1, arm2, arm3));
-                }
-            }
+        let arms = if s.contains(&[',', '_'][..]) {
+            s.split(&[',', '_'][..])
+                .map(|arm| arm.trim())
+                .collect::<Vec<_>>()
+        } else {
+            s.split_whitespace().collect::<Vec<_>>()
+        };
+        if arms.len() != 3 {
+            return Err(ParseTriadError::ArmCount(arms.len()));
+        }
+        if let Some(c) = arms
+            .iter()
+            .flat_map(|arm| arm.chars())
+            .find(|&c| c != '0' && c != '1')
+        {
+            return Err(ParseTriadError::InvalidCharacter(c));
         }
 
-        Err("Unable to parse triad from the given string slice!")
+        Ok(Triad::from_strs(arms[0], arms[1], arms[2]))
     }
 }
 
@@ -145,11 +306,27 @@ impl From<&Triad> for AdjacencyList<u32> {
     }
 }
 
+/// Builds a dense graph from a triad. The vertices are labeled as in the
+/// adjacencylist and their indices coincide with their labels.
+impl From<&Triad> for DenseGraph<u32> {
+    fn from(triad: &Triad) -> Self {
+        let list = AdjacencyList::<u32>::from(triad);
+        let mut graph = DenseGraph::new();
+        for v in 0..list.vertices().count() as u32 {
+            graph.add_vertex(v);
+        }
+        for (u, v) in list.edges() {
+            graph.add_edge(&u, &v);
+        }
+        graph
+    }
+}
+
 impl TryFrom<AdjacencyList<u32>> for Triad {
     type Error = &'static str;
 
     fn try_from(list: AdjacencyList<u32>) -> Result<Self, Self::Error> {
-        let mut edges = list.edges().into_iter().collect::<HashSet<_>>();
+        let mut edges = list.edges().collect::<HashSet<_>>();
         let mut triad_vec = Vec::<(u32, String)>::new();
 
         for u in list.vertices() {
@@ -169,7 +346,7 @@ impl TryFrom<AdjacencyList<u32>> for Triad {
         }
 
         triad_vec.sort_by_key(|(i, _)| *i);
-        if let Some((_, arm1)) = triad_vec.get(0) {
+        if let Some((_, arm1)) = triad_vec.first() {
             if let Some((_, arm2)) = triad_vec.get(1) {
                 if let Some((_, arm3)) = triad_vec.get(2) {
                     return Ok(Triad::from_strs(arm1, arm2, arm3));
@@ -208,39 +385,30 @@ fn rooted_core_arms(max_len: u32) -> Vec<Vec<String>> {
     let mut last = vec![String::new()];
 
     for len in 1..=max_len {
-        let path = format!("{}/arms/arms{}", Globals::get().data, len);
-        let mut arm_list_len = Vec::new();
-
-        if let Ok(file) = fs::read(&path) {
-            let arms = String::from_utf8_lossy(&file)
-                .split_terminator('\n')
-                .map(|x| x.to_string())
-                .collect();
-            arm_list_len = arms;
-        } else if let Ok(mut file) = fs::OpenOptions::new().append(true).create(true).open(&path) {
+        let cache = CacheFile::new(
+            format!("{}/arms/arms{}", Globals::get().data, len),
+            &[("length", len.to_string())],
+            Globals::get().format,
+        );
+
+        let arm_list_len = if let Some(arms) = cache.load() {
+            arms
+        } else {
+            let mut arms = Vec::new();
             for arm in &last {
-                arm_list_len.push(format!("{}{}", '0', arm.clone()));
-                arm_list_len.push(format!("{}{}", '1', arm.clone()));
+                arms.push(format!("{}{}", '0', arm.clone()));
+                arms.push(format!("{}{}", '1', arm.clone()));
             }
 
-            arm_list_len = arm_list_len
-                .iter()
-                .cloned()
-                .filter(|arm| {
-                    let mut triad = Triad::new();
-                    triad.add_arm(arm);
-
-                    if triad.is_rooted_core() {
-                        if let Err(e) = writeln!(file, "{}", arm) {
-                            eprintln!("Couldn't write to file: {}", e);
-                        }
-                        return true;
-                    }
-                    false
-                })
-                .collect();
-        } else {
-            panic!("Could not create file: {}", &path);
+            arms.retain(|arm| {
+                let mut triad = Triad::new();
+                triad.add_arm(arm);
+                triad.is_rooted_core()
+            });
+            if let Err(e) = cache.write(&arms) {
+                eprintln!("Couldn't write to file {}: {}", cache.path(), e);
+            }
+            arms
         };
         last = arm_list_len.clone();
         arm_list.push(arm_list_len);
@@ -248,9 +416,12 @@ fn rooted_core_arms(max_len: u32) -> Vec<Vec<String>> {
     arm_list
 }
 
+// A pair of arms, each given by its length and its index in the arm list
+type Pair = ((u32, usize), (u32, usize));
+
 // A cache to speed up the generation of core triads
 struct Cache {
-    pairs: HashSet<((u32, usize), (u32, usize))>,
+    pairs: HashSet<Pair>,
     counter: u32,
 }
 
@@ -280,15 +451,16 @@ impl Cache {
     }
 
     fn populate(&mut self, num: u32, arm_list: &[Vec<String>], cons: &Constraint) {
-        let path = format!("{}/nodes/pairs_{}", Globals::get().data, num);
-
-        if let Ok(pairs_vec) = FileParser::read_pairs(&path) {
-            for pair in pairs_vec {
-                self.pairs.insert(pair);
-            }
-        } else if let Ok(file) = fs::OpenOptions::new().append(true).create(true).open(&path) {
-            let file_locked = Mutex::new(file);
-            let pairs_locked = Mutex::new(Some(Vec::<_>::new()));
+        let cache = CacheFile::new(
+            format!("{}/{}/pairs_{}", Globals::get().data, cons, num),
+            &[("constraint", cons.to_string()), ("num", num.to_string())],
+            Globals::get().format,
+        );
+
+        if let Some(pairs) = cache.load::<Pair>() {
+            self.pairs.extend(pairs);
+        } else {
+            let pairs_locked = Mutex::new(Vec::<Pair>::new());
 
             cons.pairs(num).par_iter().for_each(|[i, j]| {
                 for (a, arm1) in arm_list[*i as usize].iter().enumerate() {
@@ -299,28 +471,16 @@ impl Cache {
 
                         // First condition excludes permutations of arms with the same length
                         if (i == j && a < b) || !t.is_rooted_core() {
-                            pairs_locked
-                                .lock()
-                                .unwrap()
-                                .as_mut()
-                                .unwrap()
-                                .push(((*i as u32, a), (*j as u32, b)));
-
-                            if let Err(e) =
-                                writeln!(file_locked.lock().unwrap(), "{},{},{},{}", i, a, j, b)
-                            {
-                                eprintln!("Could not write to file: {}", e);
-                            }
+                            pairs_locked.lock().unwrap().push(((*i, a), (*j, b)));
                         }
                     }
                 }
             });
-            let pairs = pairs_locked.lock().unwrap().take().unwrap();
-            for pair in &pairs {
-                self.pairs.insert(*pair);
+            let pairs = pairs_locked.into_inner().unwrap();
+            if let Err(e) = cache.write(&pairs) {
+                eprintln!("Could not write to file {}: {}", cache.path(), e);
             }
-        } else {
-            panic!("Could not create file: {}", &path);
+            self.pairs.extend(pairs);
         }
     }
 }
@@ -337,7 +497,7 @@ impl Constraint {
             // Triplets of arm lengths of triads with num nodes
             Constraint::Nodes => {
                 let mut vec = Vec::<[u32; 3]>::new();
-                if num < 8 {
+                if num < 4 {
                     return vec;
                 }
 
@@ -451,56 +611,313 @@ fn cores(num: u32, cons: &Constraint) -> Vec<Triad> {
     cores_range(num..=num, cons).into_iter().flatten().collect()
 }
 
+/// Returns all triads whose longest arm has length `len`, up to permutation of
+/// the arms.
+pub fn triads_length(len: u32) -> Vec<Triad> {
+    _triads(len, &Constraint::Length)
+}
+
+/// Returns all triads with `num` nodes, up to permutation of the arms.
+pub fn triads_nodes(num: u32) -> Vec<Triad> {
+    _triads(num, &Constraint::Nodes)
+}
+
+/// Returns all triads whose longest arm has a length contained in `range`, up
+/// to permutation of the arms.
+pub fn triads_length_range<R>(range: R) -> Vec<Vec<Triad>>
+where
+    R: RangeIter<u32>,
+{
+    range.map(|len| _triads(len, &Constraint::Length)).collect()
+}
+
+/// Returns all triads whose number of nodes is contained in `range`, up to
+/// permutation of the arms.
+pub fn triads_nodes_range<R>(range: R) -> Vec<Vec<Triad>>
+where
+    R: RangeIter<u32>,
+{
+    range.map(|num| _triads(num, &Constraint::Nodes)).collect()
+}
+
+fn _triads(num: u32, cons: &Constraint) -> Vec<Triad> {
+    let file = CacheFile::new(
+        format!("{}/{}/triads_{}", Globals::get().data, cons, num),
+        &[
+            ("constraint", cons.to_string()),
+            ("num", num.to_string()),
+            ("class", "all".into()),
+        ],
+        Globals::get().format,
+    );
+
+    if let Some(triads) = file.load() {
+        return triads;
+    }
+
+    let triadlist = Mutex::new(Vec::<Triad>::new());
+    cons.triplets(num).par_iter().for_each(|&[i, j, k]| {
+        let mut list = Vec::new();
+        let (arms1, arms2, arms3) = (arms(i), arms(j), arms(k));
+
+        for (a, arm1) in arms1.iter().enumerate() {
+            for (b, arm2) in arms2.iter().enumerate() {
+                // Excludes permutations of arms with the same length
+                if i == j && b < a {
+                    continue;
+                }
+                for (c, arm3) in arms3.iter().enumerate() {
+                    if j == k && c < b {
+                        continue;
+                    }
+                    list.push(Triad::from_strs(arm1, arm2, arm3));
+                }
+            }
+        }
+        triadlist.lock().unwrap().extend(list);
+    });
+    let list = triadlist.into_inner().unwrap();
+    if let Err(e) = file.write(&list) {
+        eprintln!("Could not write to file {}: {}", file.path(), e);
+    }
+    list
+}
+
+// Returns all arms of length `len`.
+fn arms(len: u32) -> Vec<String> {
+    (0..1u64 << len)
+        .map(|x| format!("{:0width$b}", x, width = len as usize))
+        .collect()
+}
+
 fn _cores(arm_list: &[Vec<String>], cache: &mut Cache, num: u32, cons: &Constraint) -> Vec<Triad> {
     cache.populate_to(num, arm_list, cons);
 
-    let triadlist = Mutex::new(Some(Vec::<Triad>::new()));
-    let path = format!("{}/{}/cores_{}", Globals::get().data, cons, num);
-
-    if let Ok(triad_vec) = FileParser::read_triads(&path) {
-        for triad in triad_vec {
-            triadlist.lock().unwrap().as_mut().unwrap().push(triad);
+    let file = CacheFile::new(
+        format!("{}/{}/cores_{}", Globals::get().data, cons, num),
+        &[
+            ("constraint", cons.to_string()),
+            ("num", num.to_string()),
+            ("class", "cores".into()),
+        ],
+        Globals::get().format,
+    );
+
+    if let Some(triads) = file.load() {
+        return triads;
+    }
+    // There are no core triads with less than 8 nodes
+    if let Constraint::Nodes = cons {
+        if num < 8 {
+            return Vec::new();
         }
-    } else if let Ok(file) = fs::OpenOptions::new().append(true).create(true).open(&path) {
-        let file_locked = Mutex::new(file);
+    }
 
-        cons.triplets(num).par_iter().for_each(|[i, j, k]| {
-            for (a, arm1) in arm_list[*i as usize].iter().enumerate() {
-                for (b, arm2) in arm_list[*j as usize].iter().enumerate() {
-                    for (c, arm3) in arm_list[*k as usize].iter().enumerate() {
-                        let mut count = 0;
+    let triadlist = Mutex::new(Vec::<Triad>::new());
+    cons.triplets(num).par_iter().for_each(|[i, j, k]| {
+        for (a, arm1) in arm_list[*i as usize].iter().enumerate() {
+            for (b, arm2) in arm_list[*j as usize].iter().enumerate() {
+                for (c, arm3) in arm_list[*k as usize].iter().enumerate() {
+                    let mut count = 0;
 
-                        for arm in &[arm1, arm2, arm3] {
-                            if arm.starts_with('1') {
-                                count += 1;
-                            }
-                        }
-                        if count > 1 {
-                            continue;
-                        }
-                        if cache.cached((*i, a), (*j, b), (*k, c)) {
-                            continue;
-                        }
-                        let triad = Triad::from_strs(arm1, arm2, arm3);
-                        if triad.is_core() {
-                            triadlist.lock().unwrap().as_mut().unwrap().push(triad);
-                            if let Err(e) =
-                                writeln!(file_locked.lock().unwrap(), "{},{},{}", arm1, arm2, arm3)
-                            {
-                                eprintln!("Could not write to file: {}", e);
-                            }
+                    for arm in &[arm1, arm2, arm3] {
+                        if arm.starts_with('1') {
+                            count += 1;
                         }
                     }
+                    if count > 1 {
+                        continue;
+                    }
+                    if cache.cached((*i, a), (*j, b), (*k, c)) {
+                        continue;
+                    }
+                    let triad = Triad::from_strs(arm1, arm2, arm3);
+                    if triad.is_core() {
+                        triadlist.lock().unwrap().push(triad);
+                    }
                 }
             }
-        });
-    } else {
-        panic!("Could not create file: {}", &path);
+        }
+    });
+    let list = triadlist.into_inner().unwrap();
+    if let Err(e) = file.write(&list) {
+        eprintln!("Could not write to file {}: {}", file.path(), e);
     }
-    let list = triadlist.lock().unwrap().take().unwrap();
     list
 }
 
+impl Entry for String {
+    const KIND: &'static str = "arms";
+
+    fn to_text(&self) -> String {
+        self.clone()
+    }
+
+    fn from_text(line: &str) -> Option<Self> {
+        Some(line.into())
+    }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        write_bits(buf, self);
+    }
+
+    fn decode(bytes: &[u8], pos: &mut usize) -> Option<Self> {
+        read_bits(bytes, pos)
+    }
+}
+
+impl Entry for Pair {
+    const KIND: &'static str = "pairs";
+
+    fn to_text(&self) -> String {
+        let ((i, a), (j, b)) = self;
+        format!("{},{},{},{}", i, a, j, b)
+    }
+
+    fn from_text(line: &str) -> Option<Self> {
+        let mut fields = line.split(',');
+        let mut next = || fields.next()?.parse::<usize>().ok();
+        Some(((next()? as u32, next()?), (next()? as u32, next()?)))
+    }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        let ((i, a), (j, b)) = *self;
+        for x in &[i as u64, a as u64, j as u64, b as u64] {
+            write_varint(buf, *x);
+        }
+    }
+
+    fn decode(bytes: &[u8], pos: &mut usize) -> Option<Self> {
+        let mut next = || read_varint(bytes, pos);
+        Some((
+            (next()? as u32, next()? as usize),
+            (next()? as u32, next()? as usize),
+        ))
+    }
+}
+
+impl Entry for Triad {
+    const KIND: &'static str = "triads";
+
+    fn to_text(&self) -> String {
+        self.0.join(",")
+    }
+
+    fn from_text(line: &str) -> Option<Self> {
+        let arms = line.split(',').map(|x| x.into()).collect::<Vec<_>>();
+        (arms.len() <= 3).then_some(Triad(arms))
+    }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        write_varint(buf, self.0.len() as u64);
+        for arm in &self.0 {
+            write_bits(buf, arm);
+        }
+    }
+
+    fn decode(bytes: &[u8], pos: &mut usize) -> Option<Self> {
+        let arms = read_varint(bytes, pos).filter(|&arms| arms <= 3)?;
+        (0..arms)
+            .map(|_| read_bits(bytes, pos))
+            .collect::<Option<Vec<_>>>()
+            .map(Triad)
+    }
+}
+
+/// Converts all cache files below the data directory `data` to the format
+/// `format` and selects it for the directory. Returns the number of files that
+/// were converted. Temporary files of unfinished writes are left untouched.
+/// Invalid cache files, e.g. truncated ones, are reported and removed, so that
+/// they are regenerated when needed.
+///
+/// Files without a header were written by an earlier version. If
+/// `import_legacy` is set, those with a known name get a header with the
+/// parameters given by their name, otherwise they are left untouched and
+/// regenerated when needed.
+pub fn convert_caches(data: &str, format: Format, import_legacy: bool) -> io::Result<usize> {
+    let mut count = 0;
+    for dir in &["arms", "nodes", "length"] {
+        let entries = match fs::read_dir(format!("{}/{}", data, dir)) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "tmp") {
+                continue;
+            }
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            let path = path.to_string_lossy();
+            let kind = cache_file::kind_of(&path);
+            if kind.is_none() && import_legacy {
+                let res = match legacy_params(dir, &name) {
+                    Some((String::KIND, params)) => {
+                        cache_file::import_legacy::<String>(&path, &params, format)
+                    }
+                    Some((Pair::KIND, params)) => {
+                        cache_file::import_legacy::<Pair>(&path, &params, format)
+                    }
+                    Some((Triad::KIND, params)) => {
+                        cache_file::import_legacy::<Triad>(&path, &params, format)
+                    }
+                    _ => Ok(false),
+                };
+                match res {
+                    Ok(true) => count += 1,
+                    Ok(false) => {}
+                    Err(CacheError::Io(e)) => {
+                        return Err(io::Error::new(e.kind(), format!("{}: {}", path, e)));
+                    }
+                    Err(e) => eprintln!("Skipped legacy cache file {}: {}", path, e),
+                }
+                continue;
+            }
+            let res = match kind.as_deref() {
+                Some(String::KIND) => cache_file::convert::<String>(&path, format),
+                Some(Pair::KIND) => cache_file::convert::<Pair>(&path, format),
+                Some(Triad::KIND) => cache_file::convert::<Triad>(&path, format),
+                _ => Ok(false),
+            };
+            match res {
+                Ok(true) => count += 1,
+                Ok(false) => {}
+                Err(CacheError::Io(e)) => {
+                    return Err(io::Error::new(e.kind(), format!("{}: {}", path, e)));
+                }
+                Err(e) => {
+                    eprintln!("Removed invalid cache file {}: {}", path, e);
+                    fs::remove_file(&*path)?;
+                }
+            }
+        }
+    }
+    format.select(data)?;
+    Ok(count)
+}
+
+// Returns the kind and the parameters of the cache file `name` in the
+// directory `dir` of the data directory, as they are written by `rooted_core_arms`,
+// `Cache::populate`, `_triads` and `_cores`. Returns None for unknown names.
+fn legacy_params(dir: &str, name: &str) -> Option<(&'static str, Vec<(&'static str, String)>)> {
+    let num = |prefix: &str| name.strip_prefix(prefix)?.parse::<u32>().ok();
+    if dir == "arms" {
+        return Some((String::KIND, vec![("length", num("arms")?.to_string())]));
+    }
+    let (kind, class, num) = if let Some(num) = num("pairs_") {
+        (Pair::KIND, None, num)
+    } else if let Some(num) = num("triads_") {
+        (Triad::KIND, Some("all"), num)
+    } else {
+        (Triad::KIND, Some("cores"), num("cores_")?)
+    };
+    let mut params = vec![("constraint", dir.to_string()), ("num", num.to_string())];
+    if let Some(class) = class {
+        params.push(("class", class.to_string()));
+    }
+    Some((kind, params))
+}
+
 /// A `RangeIter` iterates over a finite range.
 pub trait RangeIter<T: PartialOrd<T>>: Iterator<Item = T> {
     /// Returns the lower bound of the range (inclusive).
@@ -532,37 +949,37 @@ impl RangeIter<u32> for std::ops::RangeInclusive<u32> {
 pub struct FileParser;
 
 impl FileParser {
+    /// Reads the triads listed in the file at `path`, which is either a cache
+    /// file, in text or binary format, or a list with one triad per line in a
+    /// notation accepted by `FromStr`.
+    ///
+    /// The triads are returned in canonical form, sorted and without
+    /// duplicates.
     pub fn read_triads(path: &str) -> Result<Vec<Triad>, io::Error> {
-        let file = fs::read(&path)?;
-
-        Ok(String::from_utf8_lossy(&file)
-            .split_terminator('\n')
-            .map(|x| x.to_string())
-            .map(|t| Triad(t.split(',').map(|x| x.into()).collect::<Vec<_>>()))
-            .collect::<Vec<_>>())
-    }
-
-    fn read_pairs(path: &str) -> Result<Vec<((u32, usize), (u32, usize))>, io::Error> {
-        let file = fs::read(&path)?;
-        let s: Vec<Vec<String>> = String::from_utf8_lossy(&file)
-            .split_terminator('\n')
-            .map(|x| {
-                x.to_string()
-                    .split_terminator(',')
-                    .map(|y| y.into())
-                    .collect()
-            })
-            .collect();
-
-        let mut pairs = Vec::<_>::new();
-        for pair in s {
-            let len = pair[0].parse::<u32>().unwrap();
-            let a = pair[1].parse::<usize>().unwrap();
-            let i = pair[2].parse::<u32>().unwrap();
-            let b = pair[3].parse::<usize>().unwrap();
-            pairs.push(((len, a), (i, b)));
-        }
-        Ok(pairs)
+        let mut triads = if let Some(triads) = cache_file::read_any(path)? {
+            triads
+        } else {
+            let file = fs::read(path)?;
+            String::from_utf8_lossy(&file)
+                .split_terminator('\n')
+                .enumerate()
+                .filter(|(_, x)| !x.starts_with('#') && !x.trim().is_empty())
+                .map(|(i, x)| {
+                    x.parse::<Triad>().map_err(|e| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("{}:{}: {}", path, i + 1, e),
+                        )
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?
+        };
+        for triad in &mut triads {
+            triad.canonicalize();
+        }
+        triads.sort();
+        triads.dedup();
+        Ok(triads)
     }
 
     #[allow(dead_code)]